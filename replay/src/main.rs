@@ -0,0 +1,37 @@
+// Copyright (c) 2025 telephono
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+
+//! Replays a recorded session through the fixes outside X-Plane
+//! and lists their writes next to the recorded ones.
+//!
+//! Exits with 0 if the writes match, 1 if they differ and 2 on errors.
+
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("usage: tweaks-replay <recording>");
+        return ExitCode::from(2);
+    };
+
+    let report = fs::read(&path)
+        .map_err(tweaks::PluginError::from)
+        .and_then(|bytes| tweaks::replay::run(&bytes));
+    match report {
+        Ok(report) => {
+            print!("{report}");
+            if report.differences() == 0 {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(error) => {
+            eprintln!("{path}: {error}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::fmt::{self, Display};
+#[cfg(feature = "plugin")]
+use std::path::Path;
+use std::str::FromStr;
+
+#[cfg(feature = "plugin")]
+use crate::dataref::DataRefProvider;
+#[cfg(feature = "plugin")]
+use crate::plugin::PluginError;
+
+/// Datarefs holding the description and notes of the `.acf` file,
+/// which may mention the version
+#[cfg(feature = "plugin")]
+static VERSION_DATAREFS: [&str; 2] = [
+    "sim/aircraft/view/acf_descrip",
+    "sim/aircraft/view/acf_notes",
+];
+
+/// Files in the aircraft folder which may hold the version
+#[cfg(feature = "plugin")]
+static VERSION_FILES: [&str; 2] = ["version.txt", "Version.txt"];
+
+/// Member of the aircraft family, told apart by ICAO code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variant {
+    B461,
+    B462,
+    B463,
+}
+
+impl Variant {
+    pub fn from_icao(icao: &str) -> Option<Self> {
+        match icao {
+            "B461" => Some(Self::B461),
+            "B462" => Some(Self::B462),
+            "B463" => Some(Self::B463),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "plugin")]
+    pub const fn icao(self) -> &'static str {
+        match self {
+            Self::B461 => "B461",
+            Self::B462 => "B462",
+            Self::B463 => "B463",
+        }
+    }
+
+    /// Indices into `sim/cockpit2/electrical/bus_volts`
+    /// of the buses powering COM1 and COM2
+    pub const fn com_buses(self) -> [usize; 2] {
+        match self {
+            Self::B461 | Self::B462 => [0, 1],
+            // The stretched -300 feeds COM2 from its third bus
+            Self::B463 => [0, 2],
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::B461 => "BAe 146-100",
+            Self::B462 => "BAe 146-200",
+            Self::B463 => "BAe 146-300",
+        })
+    }
+}
+
+/// Version of the installed aircraft, e.g. `1.0.2`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AircraftVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl AircraftVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Find the first version number like `v1.0.2` or `Version 1.0`
+    /// in `text`. Numbers without the prefix are skipped,
+    /// e.g. the X-Plane version in `X-Plane 12.00`.
+    #[cfg(feature = "plugin")]
+    fn find_in(text: &str) -> Option<Self> {
+        let mut after_prefix = false;
+        text.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .find_map(|word| {
+                let number = if after_prefix {
+                    Some(word)
+                } else {
+                    word.strip_prefix(['v', 'V'])
+                };
+                after_prefix =
+                    word.trim_end_matches(':').eq_ignore_ascii_case("version");
+                number?
+                    .trim_end_matches(|c: char| !c.is_ascii_digit())
+                    .parse()
+                    .ok()
+            })
+    }
+}
+
+impl Display for AircraftVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for AircraftVersion {
+    type Err = String;
+
+    /// Parse `major.minor` or `major.minor.patch`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{value:?} is not a version number");
+
+        let mut parts = value.split('.').map(str::parse::<u32>);
+        let mut next = || parts.next().transpose().map_err(|_| invalid());
+        let (Some(major), Some(minor), patch) = (next()?, next()?, next()?)
+        else {
+            return Err(invalid());
+        };
+        if next()?.is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(major, minor, patch.unwrap_or(0)))
+    }
+}
+
+/// The loaded aircraft, handed to the components
+/// for rules which depend on the variant or version
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Aircraft {
+    pub variant: Variant,
+
+    /// `None` if the version could not be detected
+    pub version: Option<AircraftVersion>,
+}
+
+#[cfg(feature = "plugin")]
+impl Aircraft {
+    /// Find out which aircraft is loaded, fails for unsupported aircraft.
+    /// The version comes from the `.acf` description and notes
+    /// or a version file in `aircraft_dir`.
+    pub fn detect(
+        data: &dyn DataRefProvider,
+        aircraft_dir: &Path,
+    ) -> Result<Self, PluginError> {
+        let acf_icao = data
+            .find_string("sim/aircraft/view/acf_ICAO")?
+            .get_as_string()?;
+        let variant = Variant::from_icao(&acf_icao)
+            .ok_or(PluginError::AircraftNotSupported(acf_icao))?;
+
+        Ok(Self {
+            variant,
+            version: detect_version(data, aircraft_dir),
+        })
+    }
+}
+
+impl Display for Aircraft {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            Some(version) => write!(f, "{} v{version}", self.variant),
+            None => write!(f, "{}, unknown version", self.variant),
+        }
+    }
+}
+
+/// Aircraft version which fixed the bug a component works around
+pub struct Compatibility {
+    pub id: &'static str,
+
+    /// `None` while the bug is still present in the latest version
+    pub fixed_in: Option<AircraftVersion>,
+}
+
+/// Aircraft versions which fixed a bug a component works around.
+/// None of the current fixes is known to be fixed upstream yet,
+/// add an entry once Just Flight releases one.
+/// The auto throttle and TMS fixes were removed after v1.0.2.
+pub static COMPATIBILITY: &[Compatibility] = &[];
+
+/// Version of the installed aircraft which fixed the bug
+/// component `id` works around, if any
+pub fn fixed_in(
+    table: &[Compatibility],
+    id: &str,
+    installed: AircraftVersion,
+) -> Option<AircraftVersion> {
+    table
+        .iter()
+        .find(|compat| compat.id == id)
+        .and_then(|compat| compat.fixed_in)
+        .filter(|fixed_in| installed >= *fixed_in)
+}
+
+/// Find out the version of the loaded aircraft.
+/// Looks at the `.acf` description and notes first,
+/// then for a version file in `aircraft_dir`.
+#[cfg(feature = "plugin")]
+fn detect_version(
+    data: &dyn DataRefProvider,
+    aircraft_dir: &Path,
+) -> Option<AircraftVersion> {
+    let from_datarefs = VERSION_DATAREFS.iter().find_map(|name| {
+        let text = data.find_string(name).ok()?.get_as_string().ok()?;
+        AircraftVersion::find_in(&text)
+    });
+
+    from_datarefs.or_else(|| {
+        VERSION_FILES.iter().find_map(|file| {
+            let text =
+                std::fs::read_to_string(aircraft_dir.join(file)).ok()?;
+            // A version file may hold nothing but the number
+            text.trim()
+                .parse()
+                .ok()
+                .or_else(|| AircraftVersion::find_in(&text))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AircraftVersion, Compatibility, Variant, fixed_in};
+
+    #[test]
+    fn versions_parse_and_order() {
+        assert_eq!("1.0.2".parse(), Ok(AircraftVersion::new(1, 0, 2)));
+        assert_eq!("1.1".parse(), Ok(AircraftVersion::new(1, 1, 0)));
+        assert!("1".parse::<AircraftVersion>().is_err());
+        assert!("1.0.2.3".parse::<AircraftVersion>().is_err());
+        assert!("1.x".parse::<AircraftVersion>().is_err());
+        assert!(
+            AircraftVersion::new(1, 0, 10) > AircraftVersion::new(1, 0, 2)
+        );
+    }
+
+    #[test]
+    fn variants_follow_icao_code() {
+        assert_eq!(Variant::from_icao("B463"), Some(Variant::B463));
+        assert_eq!(Variant::from_icao("B744"), None);
+        assert_eq!(Variant::B461.to_string(), "BAe 146-100");
+    }
+
+    #[test]
+    #[cfg(feature = "plugin")]
+    fn versions_are_found_in_text() {
+        assert_eq!(
+            AircraftVersion::find_in("Just Flight BAe 146-200, v1.0.3."),
+            Some(AircraftVersion::new(1, 0, 3))
+        );
+        assert_eq!(
+            AircraftVersion::find_in("Version: 1.1 for X-Plane 12.00"),
+            Some(AircraftVersion::new(1, 1, 0))
+        );
+        assert_eq!(AircraftVersion::find_in("BAe 146-200"), None);
+        assert_eq!(AircraftVersion::find_in("X-Plane 12.00"), None);
+    }
+
+    #[test]
+    fn fixes_are_dropped_once_fixed_upstream() {
+        let table = [Compatibility {
+            id: "gpu",
+            fixed_in: Some(AircraftVersion::new(1, 1, 0)),
+        }];
+
+        assert_eq!(
+            fixed_in(&table, "gpu", AircraftVersion::new(1, 0, 2)),
+            None
+        );
+        assert_eq!(
+            fixed_in(&table, "gpu", AircraftVersion::new(1, 2, 0)),
+            Some(AircraftVersion::new(1, 1, 0))
+        );
+        assert_eq!(
+            fixed_in(&table, "radio", AircraftVersion::new(9, 0, 0)),
+            None
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use std::cell::RefCell;
+
+use crate::dataref::{
+    ArrayRead, ArrayReadWrite, DataRefProvider, Sample, ScalarRead,
+    ScalarReadWrite,
+};
+use crate::plugin::PluginError;
+
+/// Kind of dataref a [`Binding`] can look up
+pub trait Lookup {
+    fn lookup(
+        data: &dyn DataRefProvider,
+        name: &str,
+    ) -> Result<Box<Self>, PluginError>;
+
+    /// Current value
+    fn sample(&self) -> Sample;
+}
+
+/// Most values sampled of an array dataref
+const MAX_SAMPLED_VALUES: usize = 16;
+
+impl Lookup for dyn ScalarRead<i32> {
+    fn lookup(
+        data: &dyn DataRefProvider,
+        name: &str,
+    ) -> Result<Box<Self>, PluginError> {
+        data.find_i32(name)
+    }
+
+    fn sample(&self) -> Sample {
+        self.get().into()
+    }
+}
+
+impl Lookup for dyn ScalarReadWrite<i32> {
+    fn lookup(
+        data: &dyn DataRefProvider,
+        name: &str,
+    ) -> Result<Box<Self>, PluginError> {
+        data.find_i32_writeable(name)
+    }
+
+    fn sample(&self) -> Sample {
+        self.get().into()
+    }
+}
+
+impl Lookup for dyn ScalarRead<f32> {
+    fn lookup(
+        data: &dyn DataRefProvider,
+        name: &str,
+    ) -> Result<Box<Self>, PluginError> {
+        data.find_f32(name)
+    }
+
+    fn sample(&self) -> Sample {
+        self.get().into()
+    }
+}
+
+impl Lookup for dyn ScalarReadWrite<f32> {
+    fn lookup(
+        data: &dyn DataRefProvider,
+        name: &str,
+    ) -> Result<Box<Self>, PluginError> {
+        data.find_f32_writeable(name)
+    }
+
+    fn sample(&self) -> Sample {
+        self.get().into()
+    }
+}
+
+impl Lookup for dyn ArrayRead<f32> {
+    fn lookup(
+        data: &dyn DataRefProvider,
+        name: &str,
+    ) -> Result<Box<Self>, PluginError> {
+        data.find_f32_array(name)
+    }
+
+    fn sample(&self) -> Sample {
+        let mut values = [0.0; MAX_SAMPLED_VALUES];
+        let count = self.get(&mut values);
+        values[..count].into()
+    }
+}
+
+impl Lookup for dyn ArrayReadWrite<f32> {
+    fn lookup(
+        data: &dyn DataRefProvider,
+        name: &str,
+    ) -> Result<Box<Self>, PluginError> {
+        data.find_f32_array_writeable(name)
+    }
+
+    fn sample(&self) -> Sample {
+        let mut values = [0.0; MAX_SAMPLED_VALUES];
+        let count = self.get(&mut values);
+        values[..count].into()
+    }
+}
+
+pub type ReadI32 = Binding<dyn ScalarRead<i32>>;
+pub type WriteI32 = Binding<dyn ScalarReadWrite<i32>>;
+pub type ReadF32 = Binding<dyn ScalarRead<f32>>;
+pub type WriteF32 = Binding<dyn ScalarReadWrite<f32>>;
+pub type ReadF32Array = Binding<dyn ArrayRead<f32>>;
+pub type WriteF32Array = Binding<dyn ArrayReadWrite<f32>>;
+
+/// Whether `a` and `b` differ by at most `tolerance` everywhere
+fn within(a: &[f32], b: &[f32], tolerance: f32) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tolerance)
+}
+
+/// Whether writing `value` over `known` would change nothing
+fn unchanged(known: &Sample, value: &Sample, tolerance: f32) -> bool {
+    match (known, value) {
+        (Sample::Int(known), Sample::Int(value)) => known == value,
+        (Sample::Float(known), Sample::Float(value)) => {
+            within(&[*known], &[*value], tolerance)
+        }
+        (Sample::FloatArray(known), Sample::FloatArray(values)) => {
+            within(known, values, tolerance)
+        }
+        _ => false,
+    }
+}
+
+/// Dataref a component declares by name, type and access.
+/// Looked up on [`Bind::resolve`] until found.
+/// Reads return a default value and writes do nothing while unbound.
+///
+/// Writeable bindings remember the value last read or written
+/// and skip writes which wouldn't change it.
+/// The flight loop has them forget it before every update,
+/// as X-Plane and other plugins may change the value in between.
+pub struct Binding<D: ?Sized> {
+    name: &'static str,
+    dataref: Option<Box<D>>,
+
+    /// Largest difference of float values treated as no change
+    tolerance: f32,
+
+    /// Value last read or written since the start of the flight loop
+    known: RefCell<Option<Sample>>,
+
+    /// Whether the dataref was written since [`Bind::take_written`]
+    written: bool,
+}
+
+impl<D: ?Sized> Binding<D> {
+    /// Dataref the component can't work without
+    pub const fn required(name: &'static str) -> Self {
+        Self {
+            name,
+            dataref: None,
+            tolerance: 0.0,
+            known: RefCell::new(None),
+            written: false,
+        }
+    }
+
+    /// Skip writes of float values at most `tolerance` off the known value
+    pub const fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn remember(&self, value: Sample) {
+        *self.known.borrow_mut() = Some(value);
+    }
+}
+
+impl<T: Default> Binding<dyn ScalarRead<T>> {
+    /// Current value, the default while unbound
+    pub fn get(&self) -> T {
+        self.dataref
+            .as_deref()
+            .map_or_else(T::default, ScalarRead::get)
+    }
+}
+
+impl<T: Default + Copy + Into<Sample>> Binding<dyn ScalarReadWrite<T>> {
+    /// Current value, the default while unbound
+    pub fn get(&self) -> T {
+        self.value().unwrap_or_default()
+    }
+
+    /// Current value, `None` while unbound
+    pub fn value(&self) -> Option<T> {
+        let value = self.dataref.as_deref().map(ScalarRead::get)?;
+        self.remember(value.into());
+        Some(value)
+    }
+
+    /// Write `value` unless the dataref already holds it.
+    /// Returns whether it was written.
+    pub fn set(&mut self, value: T) -> bool {
+        let Some(dataref) = self.dataref.as_mut() else {
+            return false;
+        };
+        let sample = value.into();
+        let known = self.known.get_mut();
+        if known
+            .as_ref()
+            .is_some_and(|known| unchanged(known, &sample, self.tolerance))
+        {
+            return false;
+        }
+
+        dataref.set(value);
+        *known = Some(sample);
+        self.written = true;
+        true
+    }
+}
+
+impl<T> Binding<dyn ArrayRead<T>> {
+    /// Copies the values into `dest`
+    /// and returns the number of values copied, 0 while unbound
+    pub fn get(&self, dest: &mut [T]) -> usize {
+        self.dataref.as_ref().map_or(0, |dataref| dataref.get(dest))
+    }
+}
+
+impl Binding<dyn ArrayReadWrite<f32>> {
+    /// Copies the values into `dest`
+    /// and returns the number of values copied, 0 while unbound
+    pub fn get(&self, dest: &mut [f32]) -> usize {
+        let Some(dataref) = self.dataref.as_ref() else {
+            return 0;
+        };
+        let count = dataref.get(dest);
+        self.remember(dest[..count].into());
+        count
+    }
+
+    /// Write `values` unless the dataref already holds them.
+    /// Returns whether they were written.
+    pub fn set(&mut self, values: &[f32]) -> bool {
+        let Some(dataref) = self.dataref.as_mut() else {
+            return false;
+        };
+        let known = self.known.get_mut();
+        if let Some(Sample::FloatArray(known)) = known.as_ref()
+            && within(known, values, self.tolerance)
+        {
+            return false;
+        }
+
+        dataref.set(values);
+        *known = Some(values.into());
+        self.written = true;
+        true
+    }
+}
+
+/// Any [`Binding`], for resolving and reporting them together
+pub trait Bind {
+    fn name(&self) -> &'static str;
+
+    fn is_bound(&self) -> bool;
+
+    /// Current value, `None` while unbound
+    fn sample(&self) -> Option<Sample>;
+
+    /// Current value as text, `None` while unbound
+    #[cfg(feature = "plugin")]
+    fn show(&self) -> Option<String> {
+        self.sample().map(|sample| sample.to_string())
+    }
+
+    /// Drop the value remembered to skip unchanged writes
+    fn forget(&mut self);
+
+    /// Whether the dataref was written since the last call
+    fn take_written(&mut self) -> bool;
+
+    /// Look up the dataref unless already bound
+    fn resolve(
+        &mut self,
+        data: &dyn DataRefProvider,
+    ) -> Result<(), PluginError>;
+}
+
+impl<D: ?Sized + Lookup> Bind for Binding<D> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn is_bound(&self) -> bool {
+        self.dataref.is_some()
+    }
+
+    fn sample(&self) -> Option<Sample> {
+        self.dataref.as_deref().map(D::sample)
+    }
+
+    fn forget(&mut self) {
+        *self.known.get_mut() = None;
+    }
+
+    fn take_written(&mut self) -> bool {
+        std::mem::take(&mut self.written)
+    }
+
+    fn resolve(
+        &mut self,
+        data: &dyn DataRefProvider,
+    ) -> Result<(), PluginError> {
+        if self.dataref.is_none() {
+            self.dataref = Some(D::lookup(data, self.name)?);
+        }
+        Ok(())
+    }
+}
+
+/// Resolve each binding `bindings` visits,
+/// carrying on past missing datarefs.
+/// Fails with the first dataref which is still missing.
+pub fn resolve_all(
+    data: &dyn DataRefProvider,
+    bindings: impl FnOnce(&mut dyn FnMut(&mut dyn Bind)),
+) -> Result<(), PluginError> {
+    let mut result = Ok(());
+    bindings(&mut |binding| {
+        if let Err(error) = binding.resolve(data)
+            && result.is_ok()
+        {
+            result = Err(error);
+        }
+    });
+    result
+}
@@ -1,4 +1,127 @@
+use crate::aircraft::Aircraft;
+use crate::binding::Bind;
+use crate::event::{Event, EventKind};
+use crate::plugin::PluginError;
+
+/// Static description of a component
+#[derive(Clone, Copy, Debug)]
+pub struct ComponentInfo {
+    /// Stable identifier, e.g. for settings
+    pub id: &'static str,
+
+    /// Name shown to the user
+    pub name: &'static str,
+
+    pub description: &'static str,
+
+    /// Whether the component runs unless the user disables it
+    pub enabled_by_default: bool,
+}
+
+/// How often a component wants to be updated once initialized
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpdateRate {
+    /// Every flight loop, for fixes that must not lag a single frame
+    EveryFrame,
+
+    /// At most this many times per second.
+    /// Components asking for zero, negative or NaN rates
+    /// are updated every frame instead.
+    Hz(f32),
+
+    /// Whenever [`PluginComponent::inputs_changed`] reports a change
+    OnChange,
+}
+
+/// One-shot action of a component, offered as an X-Plane command
+#[cfg(feature = "plugin")]
+#[derive(Clone, Copy, Debug)]
+pub struct ComponentAction {
+    /// Last part of the command name
+    pub name: &'static str,
+
+    pub description: &'static str,
+}
+
 pub trait PluginComponent {
-    fn is_initialized(&self) -> bool;
+    fn info(&self) -> ComponentInfo;
+
+    fn update_rate(&self) -> UpdateRate {
+        UpdateRate::EveryFrame
+    }
+
+    /// Cheap check whether `update` has anything to do.
+    /// Called every flight loop for [`UpdateRate::OnChange`] components.
+    fn inputs_changed(&mut self) -> bool {
+        true
+    }
+
+    /// Calls `visit` with each dataref the component works with,
+    /// including those created by SASL.
+    /// Looked up before [`PluginComponent::initialize`] until all
+    /// required ones are found.
+    fn bindings(&mut self, visit: &mut dyn FnMut(&mut dyn Bind));
+
+    /// Prepare the first update once all required datarefs are bound,
+    /// e.g. take over datarefs from the simulator
+    /// or pick the rules for the loaded `aircraft`.
+    /// Called again until it succeeds.
+    fn initialize(&mut self, _aircraft: Aircraft) -> Result<(), PluginError> {
+        Ok(())
+    }
+
     fn update(&mut self);
+
+    /// Kinds of events passed to [`PluginComponent::on_event`].
+    /// The datarefs behind them are only watched while wanted.
+    fn events(&self) -> &'static [EventKind] {
+        &[]
+    }
+
+    /// React to something that just happened.
+    /// Only called while the component is initialized.
+    fn on_event(&mut self, _event: &Event) {}
+
+    #[cfg(feature = "plugin")]
+    fn actions(&self) -> &'static [ComponentAction] {
+        &[]
+    }
+
+    /// Run the action called `name` once.
+    /// Only called while the component is initialized.
+    #[cfg(feature = "plugin")]
+    fn run_action(&mut self, _name: &str) {}
+
+    /// Whether the component currently overrides the simulator,
+    /// e.g. forces a dataref to its own value
+    fn is_active(&self) -> bool {
+        false
+    }
+
+    /// Hand back everything the component took over from the simulator,
+    /// e.g. restore overrides to the values they had before.
+    #[cfg(feature = "plugin")]
+    fn teardown(&mut self) {}
+}
+
+/// Value a dataref had before a component took it over
+pub struct SavedValue<T>(Option<T>);
+
+impl<T: Copy> SavedValue<T> {
+    pub fn new() -> Self {
+        Self(None)
+    }
+
+    /// Remember `value` unless there already is a saved value
+    pub fn save(&mut self, value: T) {
+        if self.0.is_none() {
+            self.0 = Some(value);
+        }
+    }
+
+    /// Take the saved value, leaving nothing saved
+    #[cfg(feature = "plugin")]
+    pub fn take(&mut self) -> Option<T> {
+        self.0.take()
+    }
 }
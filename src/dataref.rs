@@ -0,0 +1,302 @@
+#[cfg(feature = "plugin")]
+use xplm::data::ReadOnly;
+use xplm::data::borrowed::DataRef;
+use xplm::data::owned::OwnedData;
+use xplm::data::{self, ReadWrite};
+
+use std::fmt::{self, Display};
+
+use crate::plugin::PluginError;
+
+/// Value read from or written to a dataref
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sample {
+    Int(i32),
+    Float(f32),
+    FloatArray(Vec<f32>),
+}
+
+impl From<i32> for Sample {
+    fn from(value: i32) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<f32> for Sample {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&[f32]> for Sample {
+    fn from(values: &[f32]) -> Self {
+        Self::FloatArray(values.to_vec())
+    }
+}
+
+impl Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Float(value) => write!(f, "{value}"),
+            Self::FloatArray(values) => write!(f, "{values:?}"),
+        }
+    }
+}
+
+/// Read access to a scalar dataref
+pub trait ScalarRead<T> {
+    fn get(&self) -> T;
+}
+
+/// Read and write access to a scalar dataref
+pub trait ScalarReadWrite<T>: ScalarRead<T> {
+    fn set(&mut self, value: T);
+}
+
+/// Read access to an array dataref
+pub trait ArrayRead<T> {
+    /// Copies the dataref values into `dest`
+    /// and returns the number of values copied
+    fn get(&self, dest: &mut [T]) -> usize;
+}
+
+/// Read and write access to an array dataref
+pub trait ArrayReadWrite<T>: ArrayRead<T> {
+    fn set(&mut self, values: &[T]);
+}
+
+/// Read access to a byte array dataref holding a string
+#[cfg(feature = "plugin")]
+pub trait StringRead {
+    fn get_as_string(&self) -> Result<String, PluginError>;
+}
+
+/// Looks up datarefs by name.
+/// Components only talk to the simulator through this trait,
+/// so they can run against something other than X-Plane.
+pub trait DataRefProvider {
+    fn find_i32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<i32>>, PluginError>;
+
+    fn find_i32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError>;
+
+    fn find_f32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<f32>>, PluginError>;
+
+    fn find_f32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError>;
+
+    fn find_f32_array(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayRead<f32>>, PluginError>;
+
+    fn find_f32_array_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayReadWrite<f32>>, PluginError>;
+
+    #[cfg(feature = "plugin")]
+    fn find_string(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn StringRead>, PluginError>;
+
+    /// Create a dataref owned by this plugin.
+    /// Other plugins may change its value if `writeable` is set.
+    #[cfg(feature = "plugin")]
+    fn create_i32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError>;
+
+    /// Create a dataref owned by this plugin.
+    /// Other plugins may change its value if `writeable` is set.
+    #[cfg(feature = "plugin")]
+    fn create_f32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError>;
+}
+
+/// Datarefs provided by X-Plane through the XPLM API
+#[cfg(feature = "plugin")]
+pub struct XPlaneDataRefs;
+
+#[cfg(feature = "plugin")]
+impl XPlaneDataRefs {
+    fn find<T: ?Sized>(name: &str) -> Result<DataRef<T>, PluginError> {
+        DataRef::find(name)
+            .map_err(|_| PluginError::DataRefNotFound(name.to_string()))
+    }
+
+    fn find_writeable<T: ?Sized>(
+        name: &str,
+    ) -> Result<DataRef<T, ReadWrite>, PluginError> {
+        Self::find(name)?
+            .writeable()
+            .map_err(|_| PluginError::DataRefNotWriteable(name.to_string()))
+    }
+
+    fn create<T: ?Sized, A>(
+        name: &str,
+    ) -> Result<OwnedData<T, A>, PluginError> {
+        OwnedData::create(name)
+            .map_err(|_| PluginError::DataRefNotCreated(name.to_string()))
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl DataRefProvider for XPlaneDataRefs {
+    fn find_i32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<i32>>, PluginError> {
+        Ok(Box::new(Self::find::<i32>(name)?))
+    }
+
+    fn find_i32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        Ok(Box::new(Self::find_writeable::<i32>(name)?))
+    }
+
+    fn find_f32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<f32>>, PluginError> {
+        Ok(Box::new(Self::find::<f32>(name)?))
+    }
+
+    fn find_f32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        Ok(Box::new(Self::find_writeable::<f32>(name)?))
+    }
+
+    fn find_f32_array(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayRead<f32>>, PluginError> {
+        Ok(Box::new(Self::find::<[f32]>(name)?))
+    }
+
+    fn find_f32_array_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayReadWrite<f32>>, PluginError> {
+        Ok(Box::new(Self::find_writeable::<[f32]>(name)?))
+    }
+
+    fn find_string(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn StringRead>, PluginError> {
+        Ok(Box::new(Self::find::<[u8]>(name)?))
+    }
+
+    fn create_i32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        if writeable {
+            Ok(Box::new(Self::create::<i32, ReadWrite>(name)?))
+        } else {
+            Ok(Box::new(Self::create::<i32, ReadOnly>(name)?))
+        }
+    }
+
+    fn create_f32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        if writeable {
+            Ok(Box::new(Self::create::<f32, ReadWrite>(name)?))
+        } else {
+            Ok(Box::new(Self::create::<f32, ReadOnly>(name)?))
+        }
+    }
+}
+
+impl<A> ScalarRead<i32> for DataRef<i32, A> {
+    fn get(&self) -> i32 {
+        data::DataRead::get(self)
+    }
+}
+
+impl ScalarReadWrite<i32> for DataRef<i32, ReadWrite> {
+    fn set(&mut self, value: i32) {
+        data::DataReadWrite::set(self, value);
+    }
+}
+
+impl<A> ScalarRead<f32> for DataRef<f32, A> {
+    fn get(&self) -> f32 {
+        data::DataRead::get(self)
+    }
+}
+
+impl ScalarReadWrite<f32> for DataRef<f32, ReadWrite> {
+    fn set(&mut self, value: f32) {
+        data::DataReadWrite::set(self, value);
+    }
+}
+
+impl<A> ScalarRead<i32> for OwnedData<i32, A> {
+    fn get(&self) -> i32 {
+        data::DataRead::get(self)
+    }
+}
+
+impl<A> ScalarReadWrite<i32> for OwnedData<i32, A> {
+    fn set(&mut self, value: i32) {
+        data::DataReadWrite::set(self, value);
+    }
+}
+
+impl<A> ScalarRead<f32> for OwnedData<f32, A> {
+    fn get(&self) -> f32 {
+        data::DataRead::get(self)
+    }
+}
+
+impl<A> ScalarReadWrite<f32> for OwnedData<f32, A> {
+    fn set(&mut self, value: f32) {
+        data::DataReadWrite::set(self, value);
+    }
+}
+
+impl<A> ArrayRead<f32> for DataRef<[f32], A> {
+    fn get(&self, dest: &mut [f32]) -> usize {
+        data::ArrayRead::get(self, dest)
+    }
+}
+
+impl ArrayReadWrite<f32> for DataRef<[f32], ReadWrite> {
+    fn set(&mut self, values: &[f32]) {
+        data::ArrayReadWrite::set(self, values);
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<A> StringRead for DataRef<[u8], A> {
+    fn get_as_string(&self) -> Result<String, PluginError> {
+        Ok(data::StringRead::get_as_string(self)?)
+    }
+}
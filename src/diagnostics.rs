@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use xplm::geometry::{Point, Rect};
+use xplm::window::{
+    Cursor, KeyEvent, MouseEvent, ScrollEvent, Window, WindowDelegate,
+    WindowRef,
+};
+
+use crate::draw::{self, LINE_HEIGHT, MARGIN};
+use crate::registry::{ComponentRegistry, InitState};
+
+/// State of every component and the datarefs it works with, one per line
+pub fn report(components: &mut ComponentRegistry) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for registered in components.iter_mut() {
+        let ready = registered.init == InitState::Ready;
+        let status = match &registered.init {
+            _ if !registered.enabled => "disabled".to_string(),
+            InitState::Ready if !registered.suspended.is_zero() => format!(
+                "ready, suspended for {:.0}s",
+                registered.suspended.as_secs_f32()
+            ),
+            InitState::Ready if registered.component.is_active() => {
+                "ready, active".to_string()
+            }
+            InitState::Ready => "ready".to_string(),
+            InitState::Pending { waited, reason } => format!(
+                "waiting {}s, {}",
+                waited.as_secs(),
+                reason.as_deref().unwrap_or("not tried yet")
+            ),
+            InitState::Failed { reason } => format!("FAILED, {reason}"),
+        };
+        let mut line = format!("{}: {status}", registered.info.name);
+        if registered.enabled && ready {
+            match registered.last_active {
+                Some(at) => {
+                    let _ = write!(
+                        line,
+                        ", last active {:.1}s ago",
+                        at.elapsed().as_secs_f32()
+                    );
+                }
+                None => line.push_str(", never active"),
+            }
+        }
+        lines.push(line);
+        if registered.profile.updates > 0 {
+            lines.push(format!("    profile: {}", registered.profile));
+        }
+
+        registered.component.bindings(&mut |binding| {
+            let value =
+                binding.show().unwrap_or_else(|| "missing".to_string());
+            lines.push(format!("    {} = {value}", binding.name()));
+        });
+    }
+
+    lines
+}
+
+/// Floating window showing the [`report`], refreshed every frame
+struct DiagnosticsDelegate {
+    components: Rc<RefCell<ComponentRegistry>>,
+}
+
+impl WindowDelegate for DiagnosticsDelegate {
+    fn draw(&mut self, window: &Window) {
+        let Ok(mut components) = self.components.try_borrow_mut() else {
+            return;
+        };
+        let geometry = window.geometry();
+
+        let mut y = geometry.top() - MARGIN - LINE_HEIGHT;
+        for line in report(&mut components) {
+            if y < geometry.bottom() + MARGIN {
+                break;
+            }
+            draw::text(geometry.left() + MARGIN, y, &line);
+            y -= LINE_HEIGHT;
+        }
+    }
+
+    fn keyboard_event(&mut self, _window: &Window, _event: KeyEvent) {}
+
+    fn mouse_event(&mut self, _window: &Window, _event: MouseEvent) -> bool {
+        true
+    }
+
+    fn scroll_event(&mut self, _window: &Window, _event: ScrollEvent) -> bool {
+        true
+    }
+
+    fn cursor(&mut self, _window: &Window, _position: Point<i32>) -> Cursor {
+        Cursor::Default
+    }
+}
+
+/// Diagnostics window, hidden until opened from the menu or by command
+pub fn create_window(components: Rc<RefCell<ComponentRegistry>>) -> WindowRef {
+    let geometry = Rect::from_left_top_right_bottom(100, 700, 700, 250);
+    let window = Window::new(geometry, DiagnosticsDelegate { components });
+    window.set_visible(false);
+    window
+}
@@ -0,0 +1,33 @@
+use std::ffi::CString;
+
+use xplm_sys::{XPLMDrawString, XPLMFontID, xplmFont_Proportional};
+
+/// Bindgen gives the font enum a different type on each platform
+#[allow(clippy::cast_possible_wrap)]
+const FONT_PROPORTIONAL: XPLMFontID = xplmFont_Proportional as XPLMFontID;
+
+/// Line height of the proportional font in boxels
+pub const LINE_HEIGHT: i32 = 14;
+
+/// Space between a window border and the text
+pub const MARGIN: i32 = 10;
+
+/// Draw white text with its baseline at `y`.
+/// Only valid inside a window's draw callback.
+pub fn text(x: i32, y: i32, text: &str) {
+    let Ok(text) = CString::new(text) else {
+        return;
+    };
+    let mut white = [1.0_f32, 1.0, 1.0];
+    // SAFETY: called from a draw callback with a valid C string
+    unsafe {
+        XPLMDrawString(
+            white.as_mut_ptr(),
+            x,
+            y,
+            text.as_ptr(),
+            std::ptr::null_mut(),
+            FONT_PROPORTIONAL,
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt::{self, Display};
+
+use crate::dataref::Sample;
+use crate::gpu::GPU_AVAILABLE;
+use crate::registry::ComponentRegistry;
+
+/// Something that just happened in the simulator or the plugin
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The aircraft's GPU was connected or disconnected
+    GpuConnected(bool),
+
+    /// A component was switched on or off
+    #[cfg(feature = "plugin")]
+    ComponentEnabled { id: &'static str, enabled: bool },
+
+    /// A component's updates were paused or resumed
+    #[cfg(feature = "plugin")]
+    ComponentSuspended { id: &'static str, suspended: bool },
+}
+
+/// What an [`Event`] is about, for components to ask for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    GpuConnected,
+    #[cfg(feature = "plugin")]
+    ComponentEnabled,
+    #[cfg(feature = "plugin")]
+    ComponentSuspended,
+}
+
+impl Event {
+    pub const fn kind(&self) -> EventKind {
+        match self {
+            Self::GpuConnected(_) => EventKind::GpuConnected,
+            #[cfg(feature = "plugin")]
+            Self::ComponentEnabled { .. } => EventKind::ComponentEnabled,
+            #[cfg(feature = "plugin")]
+            Self::ComponentSuspended { .. } => EventKind::ComponentSuspended,
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GpuConnected(true) => f.write_str("GPU connected"),
+            Self::GpuConnected(false) => f.write_str("GPU disconnected"),
+            #[cfg(feature = "plugin")]
+            Self::ComponentEnabled { id, enabled } => {
+                let state = if *enabled { "enabled" } else { "disabled" };
+                write!(f, "{id} {state}")
+            }
+            #[cfg(feature = "plugin")]
+            Self::ComponentSuspended { id, suspended } => {
+                let state = if *suspended { "suspended" } else { "resumed" };
+                write!(f, "{id} {state}")
+            }
+        }
+    }
+}
+
+/// Last value of something watched, to report each change once
+pub struct Edge<T>(Option<T>);
+
+impl<T: Copy + PartialEq> Edge<T> {
+    pub const fn new() -> Self {
+        Self(None)
+    }
+
+    /// Returns `value` if it differs from the previous one.
+    /// The first value is only remembered,
+    /// a state the aircraft loaded with is no transition.
+    pub fn changed(&mut self, value: T) -> Option<T> {
+        let previous = self.0.replace(value);
+        previous
+            .is_some_and(|previous| previous != value)
+            .then_some(value)
+    }
+}
+
+/// Watches datarefs the components bind
+/// and turns their transitions into [`Event`]s
+pub struct ChangeTracker {
+    gpu_connected: Edge<bool>,
+}
+
+impl ChangeTracker {
+    pub const fn new() -> Self {
+        Self {
+            gpu_connected: Edge::new(),
+        }
+    }
+
+    /// Publish the changes since the last poll,
+    /// only watching datarefs of the kinds an enabled component wants
+    pub fn poll(&mut self, components: &mut ComponentRegistry) {
+        let wanted = components.iter().any(|comp| {
+            comp.enabled
+                && comp.component.events().contains(&EventKind::GpuConnected)
+        });
+        if !wanted {
+            // A later subscriber doesn't get a stale transition
+            self.gpu_connected = Edge::new();
+            return;
+        }
+
+        if let Some(Sample::Int(available)) = components.sample(GPU_AVAILABLE)
+            && let Some(connected) = self.gpu_connected.changed(available == 1)
+        {
+            components.publish(Event::GpuConnected(connected));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Edge;
+
+    #[test]
+    fn edges_report_changes_once() {
+        let mut edge = Edge::new();
+        assert_eq!(edge.changed(false), None);
+        assert_eq!(edge.changed(false), None);
+        assert_eq!(edge.changed(true), Some(true));
+        assert_eq!(edge.changed(true), None);
+        assert_eq!(edge.changed(false), Some(false));
+    }
+}
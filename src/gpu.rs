@@ -1,104 +1,134 @@
-use xplm::data::borrowed::DataRef;
-use xplm::data::{DataRead, DataReadWrite, ReadWrite};
-use xplm::debugln;
-
-use crate::component::PluginComponent;
-use crate::plugin::PLUGIN_NAME;
+use crate::aircraft::Aircraft;
+use crate::binding::{Bind, ReadI32, WriteF32, WriteI32};
+use crate::component::{
+    ComponentInfo, PluginComponent, SavedValue, UpdateRate,
+};
+use crate::event::{Event, EventKind};
+use crate::log::{self, Level};
 use crate::plugin::PluginError;
+use crate::settings::tunables;
+
+const ID: &str = "gpu";
+
+/// Whether the aircraft's ground power unit is connected
+pub const GPU_AVAILABLE: &str = "thranda/electrical/ExtPwrGPUAvailable";
 
 /// The current GPU/external power isn't compatible with X-Plane's
 /// current GPU/external power implementation.
 /// This corrects the supplied generator voltage...
 #[allow(clippy::struct_field_names)]
 pub struct GeneratorVolts {
-    is_initialized: bool,
-
-    /// `thranda/electrical/ExtPwrGPUAvailable`
-    thranda_gpu_available: Option<DataRef<i32>>,
-
-    /// `sim/cockpit2/electrical/GPU_generator_volts`
-    gpu_generator_volts: Option<DataRef<f32, ReadWrite>>,
-
-    /// `sim/operation/override/override_GPU_volts`
-    override_gpu_volts: Option<DataRef<i32, ReadWrite>>,
+    thranda_gpu_available: ReadI32,
+    gpu_generator_volts: WriteF32,
+    override_gpu_volts: WriteI32,
+    saved_override_gpu_volts: SavedValue<i32>,
 }
 
 impl GeneratorVolts {
     pub fn new() -> Self {
         Self {
-            is_initialized: false,
-
-            gpu_generator_volts: None,
-            override_gpu_volts: None,
-            thranda_gpu_available: None,
+            thranda_gpu_available: ReadI32::required(GPU_AVAILABLE),
+            gpu_generator_volts: WriteF32::required(
+                "sim/cockpit2/electrical/GPU_generator_volts",
+            )
+            .with_tolerance(0.01),
+            override_gpu_volts: WriteI32::required(
+                "sim/operation/override/override_GPU_volts",
+            ),
+            saved_override_gpu_volts: SavedValue::new(),
         }
     }
+}
 
-    /// Fetch SASL datarefs if they are available
-    fn initialize(&mut self) -> Result<(), PluginError> {
-        if self.gpu_generator_volts.is_none() {
-            self.gpu_generator_volts = Some(
-                DataRef::find("sim/cockpit2/electrical/GPU_generator_volts")?
-                    .writeable()?,
-            );
-        }
-        if self.override_gpu_volts.is_none() {
-            self.override_gpu_volts = Some(
-                DataRef::find("sim/operation/override/override_GPU_volts")?
-                    .writeable()?,
-            );
-        }
-        if self.thranda_gpu_available.is_none() {
-            self.thranda_gpu_available =
-                Some(DataRef::find("thranda/electrical/ExtPwrGPUAvailable")?);
+impl PluginComponent for GeneratorVolts {
+    fn info(&self) -> ComponentInfo {
+        ComponentInfo {
+            id: ID,
+            name: "Fix GPU external power",
+            description: "Supply GPU voltage while the BAe 146 GPU is connected",
+            enabled_by_default: true,
         }
+    }
 
-        Ok(())
+    fn update_rate(&self) -> UpdateRate {
+        // Nobody notices the GPU coming online a few frames late
+        UpdateRate::Hz(4.0)
     }
-}
 
-impl PluginComponent for GeneratorVolts {
-    fn is_initialized(&self) -> bool {
-        self.is_initialized
+    fn bindings(&mut self, visit: &mut dyn FnMut(&mut dyn Bind)) {
+        visit(&mut self.gpu_generator_volts);
+        visit(&mut self.override_gpu_volts);
+        visit(&mut self.thranda_gpu_available);
+    }
+
+    fn initialize(&mut self, _aircraft: Aircraft) -> Result<(), PluginError> {
+        // Take over GPU volts from X-Plane
+        let old = self.override_gpu_volts.get();
+        self.saved_override_gpu_volts.save(old);
+        self.override_gpu_volts.set(1);
+        log::override_write(
+            Level::Info,
+            ID,
+            self.override_gpu_volts.name(),
+            old,
+            1,
+            format_args!("take over GPU volts from X-Plane"),
+        );
+
+        Ok(())
     }
 
-    #[allow(clippy::collapsible_if)]
     fn update(&mut self) {
-        // We need to wait until all datarefs created by SASL are available...
-        if !self.is_initialized {
-            if self.initialize().is_ok() {
-                if let Some(override_gpu_volts) =
-                    self.override_gpu_volts.as_mut()
-                {
-                    override_gpu_volts.set(1);
-                }
-                self.is_initialized = true;
-                debugln!(
-                    "{PLUGIN_NAME} FixGPUGeneratorVolts component initialized"
-                );
-            } else {
-                return;
-            }
+        let gpu_available = self.thranda_gpu_available.get();
+        let gpu_generator_volts = self.gpu_generator_volts.get();
+
+        // Set override GPU volts if BAe 146 GPU is connected
+        let (gpu_volts, reason) = match gpu_available {
+            1 => (tunables().gpu_volts, "GPU connected"),
+            0 => (0.0, "GPU disconnected"),
+            _ => return,
+        };
+        if self.gpu_generator_volts.set(gpu_volts) {
+            log::override_write(
+                Level::Info,
+                ID,
+                self.gpu_generator_volts.name(),
+                gpu_generator_volts,
+                gpu_volts,
+                format_args!("{reason}"),
+            );
+        }
+    }
+
+    fn events(&self) -> &'static [EventKind] {
+        &[EventKind::GpuConnected]
+    }
+
+    /// Don't wait for the next update when the GPU is plugged in or out
+    fn on_event(&mut self, event: &Event) {
+        if matches!(event, Event::GpuConnected(_)) {
+            self.update();
         }
+    }
 
-        let gpu_available =
-            self.thranda_gpu_available.as_ref().map_or(0, DataRead::get);
-        let gpu_generator_volts =
-            self.gpu_generator_volts.as_ref().map_or(0.0, DataRef::get);
+    fn is_active(&self) -> bool {
+        self.thranda_gpu_available.get() == 1
+    }
 
-        // Set override GPU volts if BAe 146 GPU is connected
-        if gpu_available == 1 && !almost::equal(gpu_generator_volts, 27.5) {
-            if let Some(gpu_generator_volts) =
-                self.gpu_generator_volts.as_mut()
-            {
-                gpu_generator_volts.set(27.5);
-            }
-        } else if gpu_available == 0 && !almost::zero(gpu_generator_volts) {
-            if let Some(gpu_generator_volts) =
-                self.gpu_generator_volts.as_mut()
-            {
-                gpu_generator_volts.set(0.0);
-            }
+    #[cfg(feature = "plugin")]
+    fn teardown(&mut self) {
+        if let Some(old) = self.override_gpu_volts.value()
+            && let Some(value) = self.saved_override_gpu_volts.take()
+        {
+            self.override_gpu_volts.set(value);
+            log::override_write(
+                Level::Info,
+                ID,
+                self.override_gpu_volts.name(),
+                old,
+                value,
+                format_args!("restore value from before the plugin"),
+            );
         }
     }
 }
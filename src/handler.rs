@@ -1,59 +1,425 @@
-use xplm::debugln;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
 use xplm::flight_loop::FlightLoopCallback;
-use xplm::menu::{CheckHandler, CheckItem};
 
-use crate::component::PluginComponent;
-use crate::plugin::{PLUGIN_NAME, SYNC_THROTTLES};
+use crate::aircraft::Aircraft;
+use crate::binding;
+use crate::component::UpdateRate;
+use crate::dataref::DataRefProvider;
+use crate::event::ChangeTracker;
+#[cfg(feature = "plugin")]
+use crate::event::Event;
+#[cfg(feature = "plugin")]
+use crate::menu::MenuCheckItems;
+#[cfg(feature = "plugin")]
+use crate::messaging::{self, Notifier};
+use crate::profile::Accesses;
+#[cfg(feature = "plugin")]
+use crate::published::PublishedDataRefs;
+#[cfg(feature = "plugin")]
+use crate::recorder::Recorder;
+use crate::registry::{ComponentRegistry, InitState, RegisteredComponent};
+use crate::settings::{self, tunables};
+
+/// Time between attempts to initialize components still waiting for datarefs
+pub const INIT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
 
-// Components
-use crate::gpu::GeneratorVolts;
-use crate::hsi::CopilotHSI;
-use crate::nosewheel_steering::NosewheelSteering;
-use crate::radio::Radio;
-use crate::throttle_levers::ThrottleLevers;
+/// When the flight loop wants to be called again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NextCall {
+    /// Components are ready and run every flight loop
+    NextLoop,
+    /// Nothing to do until the next initialization attempt
+    After(Duration),
+}
 
 pub struct FlightLoopHandler {
-    components: [Box<dyn PluginComponent>; 5],
+    components: Rc<RefCell<ComponentRegistry>>,
+    #[cfg(feature = "plugin")]
+    published: Option<PublishedDataRefs>,
+    #[cfg(feature = "plugin")]
+    check_items: Option<Rc<MenuCheckItems>>,
+    #[cfg(feature = "plugin")]
+    notifier: Option<Box<dyn Notifier>>,
+    #[cfg(feature = "plugin")]
+    recorder: Option<Rc<RefCell<Recorder>>>,
+    accesses: Option<Rc<Cell<Accesses>>>,
+    tracker: ChangeTracker,
+    summary_logged: bool,
+
+    /// Time left until pending components try to initialize again
+    until_retry: Duration,
 }
 
 impl FlightLoopHandler {
-    pub fn new() -> Self {
+    pub fn new(components: Rc<RefCell<ComponentRegistry>>) -> Self {
         Self {
-            components: [
-                Box::new(GeneratorVolts::new()),
-                Box::new(CopilotHSI::new()),
-                Box::new(NosewheelSteering::new()),
-                Box::new(Radio::new()),
-                Box::new(ThrottleLevers::new()),
-            ],
+            components,
+            #[cfg(feature = "plugin")]
+            published: None,
+            #[cfg(feature = "plugin")]
+            check_items: None,
+            #[cfg(feature = "plugin")]
+            notifier: None,
+            #[cfg(feature = "plugin")]
+            recorder: None,
+            accesses: None,
+            tracker: ChangeTracker::new(),
+            summary_logged: false,
+            until_retry: Duration::ZERO,
         }
     }
-}
 
-impl FlightLoopCallback for FlightLoopHandler {
-    fn flight_loop(&mut self, state: &mut xplm::flight_loop::LoopState<'_>) {
-        for component in &mut self.components {
-            component.update();
+    /// Keep the plugin's own datarefs up to date
+    #[cfg(feature = "plugin")]
+    pub fn with_published(mut self, published: PublishedDataRefs) -> Self {
+        self.published = Some(published);
+        self
+    }
+
+    /// Keep the menu check marks in step with dataref writes
+    #[cfg(feature = "plugin")]
+    pub fn with_check_items(
+        mut self,
+        check_items: Rc<MenuCheckItems>,
+    ) -> Self {
+        self.check_items = Some(check_items);
+        self
+    }
+
+    /// Tell other plugins when a component changes state
+    #[cfg(feature = "plugin")]
+    pub fn with_notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifier = Some(Box::new(notifier));
+        self
+    }
+
+    /// Record the datarefs the components work with while recording
+    #[cfg(feature = "plugin")]
+    pub fn with_recorder(mut self, recorder: Rc<RefCell<Recorder>>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Time component updates while `profile_updates` is set,
+    /// `accesses` counts the dataref accesses of the components
+    #[cfg(feature = "plugin")]
+    pub fn with_profiling(mut self, accesses: Rc<Cell<Accesses>>) -> Self {
+        self.accesses = Some(accesses);
+        self
+    }
+
+    /// Update all ready components once,
+    /// `elapsed` is the time since the previous update.
+    /// Pending components try to initialize every [`INIT_RETRY_INTERVAL`].
+    /// Returns `true` once no enabled component is waiting for datarefs.
+    pub fn update(&mut self, elapsed: Duration) -> bool {
+        let timeout = Duration::from_secs_f32(tunables().init_timeout_secs);
+
+        self.until_retry = self.until_retry.saturating_sub(elapsed);
+        let retry = self.until_retry.is_zero();
+        if retry {
+            self.until_retry = INIT_RETRY_INTERVAL;
+        }
+
+        let accesses =
+            self.accesses.as_deref().filter(|_| settings::profiling());
+
+        let mut components = self.components.borrow_mut();
+        let data = components.data();
+        #[cfg(feature = "plugin")]
+        self.record_reads(&mut components);
+        for registered in components.iter_mut() {
+            registered
+                .component
+                .bindings(&mut |binding| binding.forget());
+        }
+        #[cfg(feature = "plugin")]
+        if let Some(published) = self.published.as_mut()
+            && published.apply_writes(&mut components)
+            && let Some(check_items) = self.check_items.as_ref()
+        {
+            check_items.refresh(&components);
+        }
+
+        self.tracker.poll(&mut components);
+        components.count_down_suspensions(elapsed);
+        #[cfg(feature = "plugin")]
+        let changes = {
+            let events = components.dispatch_events();
+            self.notify(&components, events)
+        };
+        #[cfg(not(feature = "plugin"))]
+        components.dispatch_events();
+
+        let aircraft = components.aircraft();
+        for registered in components.iter_mut().filter(|comp| comp.enabled) {
+            match &mut registered.init {
+                InitState::Ready if !registered.suspended.is_zero() => {}
+                InitState::Ready => {
+                    Self::update_when_due(registered, elapsed, accesses);
+                }
+                InitState::Pending { waited, .. } => {
+                    *waited += elapsed;
+                    if retry {
+                        Self::initialize(
+                            registered,
+                            data.as_ref(),
+                            aircraft,
+                            timeout,
+                        );
+                    }
+                }
+                InitState::Failed { .. } => {}
+            }
+        }
+        if retry {
+            // Take the values newly initialized components bound
+            // as they are, transitions start from there
+            self.tracker.poll(&mut components);
+        }
+
+        let initialization_done = components
+            .iter()
+            .filter(|comp| comp.enabled)
+            .all(|comp| !matches!(comp.init, InitState::Pending { .. }));
+
+        if initialization_done && !self.summary_logged {
+            Self::log_summary(&components);
+        }
+        self.summary_logged = initialization_done;
+
+        #[cfg(feature = "plugin")]
+        if let Some(published) = self.published.as_mut() {
+            published.publish(&components);
+        }
+        #[cfg(feature = "plugin")]
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.borrow_mut().advance(elapsed);
+        }
+
+        // Other plugins may message this one while being told
+        drop(components);
+        #[cfg(feature = "plugin")]
+        if let Some(notifier) = self.notifier.as_ref() {
+            for (id, state, suspended) in changes {
+                notifier.state_changed(id, state, suspended);
+            }
         }
 
-        // We need to wait until all datarefs created by SASL are available...
-        let initialization_done =
-            self.components.iter().all(|comp| comp.is_initialized());
-        if initialization_done {
-            // Run flightloop callback on every flightloop from now on
-            state.call_next_loop();
+        initialization_done
+    }
+
+    /// Record what the components are about to see,
+    /// their writes are recorded as they happen
+    #[cfg(feature = "plugin")]
+    fn record_reads(&self, components: &mut ComponentRegistry) {
+        let Some(mut recorder) = self
+            .recorder
+            .as_ref()
+            .and_then(|recorder| recorder.try_borrow_mut().ok())
+        else {
+            return;
+        };
+        if !recorder.is_recording() {
+            return;
+        }
+
+        for registered in components.iter_mut() {
+            registered.component.bindings(&mut |binding| {
+                if let Some(value) = binding.sample() {
+                    recorder.read(binding.name(), value);
+                }
+            });
+        }
+    }
+
+    /// Pass component state changes on to the menu.
+    /// Returns those to tell other plugins about.
+    #[cfg(feature = "plugin")]
+    fn notify(
+        &self,
+        components: &ComponentRegistry,
+        events: Vec<Event>,
+    ) -> Vec<(&'static str, i32, Duration)> {
+        let mut changes = Vec::new();
+        for event in events {
+            let (Event::ComponentEnabled { id, .. }
+            | Event::ComponentSuspended { id, .. }) = event
+            else {
+                continue;
+            };
+
+            if let Some(check_items) = self.check_items.as_ref() {
+                check_items.refresh(components);
+            }
+            if let Some(registered) = components.get(id) {
+                changes.push((
+                    id,
+                    messaging::state(registered),
+                    registered.suspended,
+                ));
+            }
+        }
+        changes
+    }
+
+    /// Update a ready component if its [`UpdateRate`] says so.
+    /// The update is timed if `accesses` is given.
+    fn update_when_due(
+        registered: &mut RegisteredComponent,
+        elapsed: Duration,
+        accesses: Option<&Cell<Accesses>>,
+    ) {
+        registered.since_update += elapsed;
+
+        let due = match registered.rate {
+            UpdateRate::EveryFrame => true,
+            UpdateRate::Hz(hz) => {
+                registered.since_update >= Duration::from_secs_f32(1.0 / hz)
+            }
+            UpdateRate::OnChange => {
+                let changed = registered.component.inputs_changed();
+                if changed {
+                    trace!("{} inputs changed", registered.info.id);
+                }
+                changed
+            }
+        };
+
+        if !due {
+            return;
+        }
+        match accesses {
+            Some(accesses) => {
+                let before = accesses.get();
+                let start = Instant::now();
+                registered.component.update();
+                registered
+                    .profile
+                    .record(start.elapsed(), accesses.get() - before);
+            }
+            None => registered.component.update(),
+        }
+        registered.since_update = Duration::ZERO;
+        Self::note_activity(registered);
+    }
+
+    /// Remember the time if the component just wrote a dataref
+    /// or overrides the simulator
+    fn note_activity(registered: &mut RegisteredComponent) {
+        let mut wrote = false;
+        registered
+            .component
+            .bindings(&mut |binding| wrote |= binding.take_written());
+        if wrote || registered.component.is_active() {
+            registered.last_active = Some(Instant::now());
+        }
+    }
+
+    /// Try to bind the datarefs of a pending component and initialize it.
+    /// Ready components run their first update right away.
+    fn initialize(
+        registered: &mut RegisteredComponent,
+        data: &dyn DataRefProvider,
+        aircraft: Aircraft,
+        timeout: Duration,
+    ) {
+        let InitState::Pending { waited, reason } = &registered.init else {
+            return;
+        };
+        let name = registered.info.name;
+        let component = &mut registered.component;
+
+        let result =
+            binding::resolve_all(data, |visit| component.bindings(visit))
+                .and_then(|()| component.initialize(aircraft));
+        registered.init = match result {
+            Ok(()) => {
+                info!("{name} initialized");
+                component.update();
+                registered.since_update = Duration::ZERO;
+                Self::note_activity(registered);
+                InitState::Ready
+            }
+            Err(error) if *waited >= timeout => {
+                error!("{name} gave up after {}s: {error}", waited.as_secs());
+                component.bindings(&mut |binding| {
+                    if !binding.is_bound() {
+                        error!("{name} is missing {}", binding.name());
+                    }
+                });
+                InitState::Failed {
+                    reason: error.to_string(),
+                }
+            }
+            Err(error) => {
+                let error = error.to_string();
+                // Only log when a different dataref is missing
+                if reason.as_ref() != Some(&error) {
+                    debug!("{name} waiting for initialization, {error}");
+                }
+                InitState::Pending {
+                    waited: *waited,
+                    reason: Some(error),
+                }
+            }
+        };
+    }
+
+    /// Run one flight loop, `since_last_call` is the time since the
+    /// previous one. Returns when the flight loop should run again.
+    pub fn run_loop(&mut self, since_last_call: Duration) -> NextCall {
+        // Components initialize as soon as their SASL datarefs show up...
+        self.update(since_last_call);
+        if self.any_ready() {
+            NextCall::NextLoop
         } else {
-            debugln!("{PLUGIN_NAME} waiting for initialization...");
+            NextCall::After(INIT_RETRY_INTERVAL)
         }
     }
-}
 
-pub struct SyncThrottlesMenuHandler;
+    /// Whether no enabled component waited for datarefs in the last update
+    #[cfg(all(test, feature = "plugin"))]
+    pub const fn initialized(&self) -> bool {
+        self.summary_logged
+    }
+
+    /// Whether any enabled component needs to run every flight loop
+    fn any_ready(&self) -> bool {
+        self.components
+            .borrow()
+            .iter()
+            .any(|comp| comp.enabled && comp.init == InitState::Ready)
+    }
+
+    fn log_summary(components: &ComponentRegistry) {
+        info!("initialization summary:");
+        for registered in components.iter() {
+            let name = registered.info.name;
+            match &registered.init {
+                _ if !registered.enabled => {
+                    info!("  {name}: disabled");
+                }
+                InitState::Ready => info!("  {name}: ready"),
+                InitState::Failed { reason } => {
+                    warn!("  {name}: FAILED, {reason}");
+                }
+                InitState::Pending { .. } => {
+                    info!("  {name}: waiting");
+                }
+            }
+        }
+    }
+}
 
-impl CheckHandler for SyncThrottlesMenuHandler {
-    fn item_checked(&mut self, _item: &CheckItem, checked: bool) {
-        if let Ok(mut sync_throttles) = SYNC_THROTTLES.lock() {
-            *sync_throttles = checked;
+impl FlightLoopCallback for FlightLoopHandler {
+    fn flight_loop(&mut self, state: &mut xplm::flight_loop::LoopState<'_>) {
+        match self.run_loop(state.since_last_call()) {
+            NextCall::NextLoop => state.call_next_loop(),
+            NextCall::After(delay) => state.call_after(delay),
         }
     }
 }
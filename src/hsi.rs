@@ -1,145 +1,126 @@
-use xplm::data::borrowed::DataRef;
-use xplm::data::{DataRead, DataReadWrite, ReadWrite};
-use xplm::debugln;
+use crate::binding::{Bind, ReadF32, ReadI32, WriteF32};
+#[cfg(feature = "plugin")]
+use crate::component::ComponentAction;
+use crate::component::{ComponentInfo, PluginComponent};
+use crate::log::{self, Level};
+use crate::settings::tunables;
 
-use crate::component::PluginComponent;
-use crate::plugin::PLUGIN_NAME;
-use crate::plugin::PluginError;
+const ID: &str = "copilot_hsi";
 
 /// Fix copilot HSI when both HSI are in RNAV mode
 pub struct CopilotHSI {
-    is_initialized: bool,
-
-    /// `sim/cockpit/switches/HSI_selector`
-    hsi_selector: Option<DataRef<i32>>,
-
-    /// `sim/cockpit/switches/HSI_selector2`
-    hsi_selector2: Option<DataRef<i32>>,
-
-    /// `sim/cockpit2/radios/actuators/hsi_obs_deg_mag_pilot`
-    hsi_obs_deg_mag_pilot: Option<DataRef<f32>>,
-
-    /// `sim/cockpit2/radios/actuators/hsi_obs_deg_mag_copilot`
-    hsi_obs_deg_mag_copilot: Option<DataRef<f32, ReadWrite>>,
-
-    /// `thranda/anim/hsiHdefDotsPilot`
-    thranda_hsi_hdef_dots_pilot: Option<DataRef<f32>>,
-
-    /// `thranda/anim/hsiHdefDotsCoPilot`
-    thranda_hsi_hdef_dots_copilot: Option<DataRef<f32, ReadWrite>>,
+    hsi_selector: ReadI32,
+    hsi_selector2: ReadI32,
+    hsi_obs_deg_mag_pilot: ReadF32,
+    hsi_obs_deg_mag_copilot: WriteF32,
+    thranda_hsi_hdef_dots_pilot: ReadF32,
+    thranda_hsi_hdef_dots_copilot: WriteF32,
 }
 
 impl CopilotHSI {
     pub fn new() -> Self {
         Self {
-            is_initialized: false,
-
-            hsi_selector: None,
-            hsi_selector2: None,
-            hsi_obs_deg_mag_pilot: None,
-            hsi_obs_deg_mag_copilot: None,
-            thranda_hsi_hdef_dots_pilot: None,
-            thranda_hsi_hdef_dots_copilot: None,
-        }
-    }
-
-    /// Fetch SASL datarefs if they are available
-    fn initialize(&mut self) -> Result<(), PluginError> {
-        if self.hsi_selector.is_none() {
-            self.hsi_selector =
-                Some(DataRef::find("sim/cockpit/switches/HSI_selector")?);
-        }
-
-        if self.hsi_selector2.is_none() {
-            self.hsi_selector2 =
-                Some(DataRef::find("sim/cockpit/switches/HSI_selector2")?);
-        }
-
-        if self.hsi_obs_deg_mag_pilot.is_none() {
-            self.hsi_obs_deg_mag_pilot = Some(DataRef::find(
+            hsi_selector: ReadI32::required(
+                "sim/cockpit/switches/HSI_selector",
+            ),
+            hsi_selector2: ReadI32::required(
+                "sim/cockpit/switches/HSI_selector2",
+            ),
+            hsi_obs_deg_mag_pilot: ReadF32::required(
                 "sim/cockpit2/radios/actuators/hsi_obs_deg_mag_pilot",
-            )?);
+            ),
+            hsi_obs_deg_mag_copilot: WriteF32::required(
+                "sim/cockpit2/radios/actuators/hsi_obs_deg_mag_copilot",
+            )
+            .with_tolerance(0.001),
+            thranda_hsi_hdef_dots_pilot: ReadF32::required(
+                "thranda/anim/hsiHdefDotsPilot",
+            ),
+            thranda_hsi_hdef_dots_copilot: WriteF32::required(
+                "thranda/anim/hsiHdefDotsCoPilot",
+            )
+            .with_tolerance(0.001),
         }
+    }
 
-        if self.hsi_obs_deg_mag_copilot.is_none() {
-            self.hsi_obs_deg_mag_copilot = Some(
-                DataRef::find(
-                    "sim/cockpit2/radios/actuators/hsi_obs_deg_mag_copilot",
-                )?
-                .writeable()?,
+    fn copy_pilot_to_copilot(&mut self) {
+        let hsi_obs_deg_mag_pilot = self.hsi_obs_deg_mag_pilot.get();
+        let hsi_obs_deg_mag_copilot = self.hsi_obs_deg_mag_copilot.get();
+        let thranda_hsi_hdef_dots_pilot =
+            self.thranda_hsi_hdef_dots_pilot.get();
+        let thranda_hsi_hdef_dots_copilot =
+            self.thranda_hsi_hdef_dots_copilot.get();
+
+        if self.hsi_obs_deg_mag_copilot.set(hsi_obs_deg_mag_pilot) {
+            log::override_write(
+                Level::Trace,
+                ID,
+                self.hsi_obs_deg_mag_copilot.name(),
+                hsi_obs_deg_mag_copilot,
+                hsi_obs_deg_mag_pilot,
+                format_args!("copilot course follows pilot course"),
             );
         }
 
-        if self.thranda_hsi_hdef_dots_pilot.is_none() {
-            self.thranda_hsi_hdef_dots_pilot =
-                Some(DataRef::find("thranda/anim/hsiHdefDotsPilot")?);
-        }
-
-        if self.thranda_hsi_hdef_dots_copilot.is_none() {
-            self.thranda_hsi_hdef_dots_copilot = Some(
-                DataRef::find("thranda/anim/hsiHdefDotsCoPilot")?
-                    .writeable()?,
+        if self
+            .thranda_hsi_hdef_dots_copilot
+            .set(thranda_hsi_hdef_dots_pilot)
+        {
+            log::override_write(
+                Level::Trace,
+                ID,
+                self.thranda_hsi_hdef_dots_copilot.name(),
+                thranda_hsi_hdef_dots_copilot,
+                thranda_hsi_hdef_dots_pilot,
+                format_args!("copilot deviation follows pilot deviation"),
             );
         }
-
-        Ok(())
     }
 }
 
 impl PluginComponent for CopilotHSI {
-    fn is_initialized(&self) -> bool {
-        self.is_initialized
-    }
-
-    fn update(&mut self) {
-        // We need to wait until all datarefs created by SASL are available...
-        if !self.is_initialized {
-            if self.initialize().is_ok() {
-                self.is_initialized = true;
-                debugln!("{PLUGIN_NAME} FixCopilotHSI component initialized");
-            } else {
-                return;
-            }
+    fn info(&self) -> ComponentInfo {
+        ComponentInfo {
+            id: ID,
+            name: "Fix copilot HSI",
+            description: "Copy pilot course and deviation to the copilot HSI when both HSIs are in RNAV mode",
+            enabled_by_default: true,
         }
+    }
 
-        let hsi_selector = self.hsi_selector.as_ref().map_or(0, DataRead::get);
-        let hsi_selector2 =
-            self.hsi_selector2.as_ref().map_or(0, DataRead::get);
+    fn bindings(&mut self, visit: &mut dyn FnMut(&mut dyn Bind)) {
+        visit(&mut self.hsi_selector);
+        visit(&mut self.hsi_selector2);
+        visit(&mut self.hsi_obs_deg_mag_pilot);
+        visit(&mut self.hsi_obs_deg_mag_copilot);
+        visit(&mut self.thranda_hsi_hdef_dots_pilot);
+        visit(&mut self.thranda_hsi_hdef_dots_copilot);
+    }
 
+    fn update(&mut self) {
         // If both HSIs are in RNAV mode...
-        if hsi_selector == 2 && hsi_selector2 == 2 {
-            let hsi_obs_deg_mag_pilot = self
-                .hsi_obs_deg_mag_pilot
-                .as_ref()
-                .map_or(0.0, DataRead::get);
-            let hsi_obs_deg_mag_copilot = self
-                .hsi_obs_deg_mag_copilot
-                .as_ref()
-                .map_or(0.0, DataRead::get);
-            let thranda_hsi_hdef_dots_pilot = self
-                .thranda_hsi_hdef_dots_pilot
-                .as_ref()
-                .map_or(0.0, DataRead::get);
-            let thranda_hsi_hdef_dots_copilot = self
-                .thranda_hsi_hdef_dots_copilot
-                .as_ref()
-                .map_or(0.0, DataRead::get);
+        if self.is_active() {
+            self.copy_pilot_to_copilot();
+        }
+    }
 
-            if !almost::equal(hsi_obs_deg_mag_pilot, hsi_obs_deg_mag_copilot)
-                && let Some(hsi_obs_deg_mag_copilot) =
-                    self.hsi_obs_deg_mag_copilot.as_mut()
-            {
-                hsi_obs_deg_mag_copilot.set(hsi_obs_deg_mag_pilot);
-            }
+    #[cfg(feature = "plugin")]
+    fn actions(&self) -> &'static [ComponentAction] {
+        &[ComponentAction {
+            name: "sync_now",
+            description: "Copy pilot course and deviation to the copilot HSI",
+        }]
+    }
 
-            if !almost::equal(
-                thranda_hsi_hdef_dots_pilot,
-                thranda_hsi_hdef_dots_copilot,
-            ) && let Some(thranda_hsi_hdef_dots_copilot) =
-                self.thranda_hsi_hdef_dots_copilot.as_mut()
-            {
-                thranda_hsi_hdef_dots_copilot.set(thranda_hsi_hdef_dots_pilot);
-            }
+    #[cfg(feature = "plugin")]
+    fn run_action(&mut self, name: &str) {
+        if name == "sync_now" {
+            self.copy_pilot_to_copilot();
         }
     }
+
+    fn is_active(&self) -> bool {
+        let rnav = tunables().hsi_rnav_selector;
+        self.hsi_selector.get() == rnav && self.hsi_selector2.get() == rnav
+    }
 }
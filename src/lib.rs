@@ -1,11 +1,75 @@
 // Copyright (c) 2025 telephono
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 
+#[cfg(feature = "plugin")]
 use xplm::xplane_plugin;
 
+/// Write a line to X-Plane's Log.txt.
+/// Headless test and replay builds have no XPLM to link against
+/// and print to stderr.
+macro_rules! debugln {
+    ($($arg:tt)*) => {{
+        #[cfg(all(not(test), feature = "plugin"))]
+        xplm::debugln!($($arg)*);
+        #[cfg(any(test, not(feature = "plugin")))]
+        eprintln!($($arg)*);
+    }};
+}
+
+/// Log an entry at a [`log::Level`] if the verbosity setting allows it
+macro_rules! log {
+    ($level:ident, $($arg:tt)*) => {
+        crate::log::write(crate::log::Level::$level, format_args!($($arg)*))
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { log!(Error, $($arg)*) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => { log!(Warn, $($arg)*) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { log!(Info, $($arg)*) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { log!(Debug, $($arg)*) };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => { log!(Trace, $($arg)*) };
+}
+
+mod aircraft;
+mod binding;
 mod component;
+mod dataref;
+#[cfg(feature = "plugin")]
+mod diagnostics;
+#[cfg(feature = "plugin")]
+mod draw;
+mod event;
 mod handler;
+mod log;
+#[cfg(feature = "plugin")]
+mod menu;
+#[cfg(feature = "plugin")]
+mod messaging;
 mod plugin;
+mod profile;
+#[cfg(feature = "plugin")]
+mod published;
+mod recorder;
+mod registry;
+pub mod replay;
+mod settings;
+#[cfg(feature = "plugin")]
+mod settings_window;
+#[cfg(feature = "plugin")]
+mod xplane;
 
 // Components
 mod gpu;
@@ -14,4 +78,15 @@ mod nosewheel_steering;
 mod radio;
 mod throttle_levers;
 
-xplane_plugin!(plugin::TweaksPlugin);
+// Simulator stand-in and scenario tests, which run the whole plugin
+#[cfg(all(test, feature = "plugin"))]
+mod scenario;
+#[cfg(all(test, feature = "plugin"))]
+mod sim;
+#[cfg(all(test, feature = "plugin"))]
+mod tests;
+
+pub use plugin::PluginError;
+
+#[cfg(feature = "plugin")]
+xplane_plugin!(xplane::TweaksPlugin);
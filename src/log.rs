@@ -0,0 +1,284 @@
+use std::fmt::{self, Display};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::plugin::PLUGIN_NAME;
+
+/// Name of the optional log file in the plugin folder
+#[cfg(feature = "plugin")]
+pub static LOG_FILE_NAME: &str = "tweaks.log";
+
+/// Size at which the log file is moved to `tweaks.log.1`
+const MAX_LOG_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Most verbose level currently logged
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Importance of a log entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        })
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err(format!(
+                "{value:?} is not one of error, warn, info, debug, trace"
+            )),
+        }
+    }
+}
+
+/// Log file which is rotated once it grows too large
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+
+    /// Size at which to rotate, grows while rotating fails
+    rotate_at: u64,
+}
+
+impl LogFile {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size,
+            rotate_at: MAX_LOG_FILE_SIZE,
+        })
+    }
+
+    /// Move the file to `tweaks.log.1` and start a new one.
+    /// The file is closed first, Windows can't rename open files.
+    /// If that fails, the file grows on and rotation is tried again later.
+    fn rotate(self) -> std::io::Result<Self> {
+        let Self {
+            path,
+            file,
+            rotate_at,
+            ..
+        } = self;
+        drop(file);
+
+        let mut rotated = path.clone().into_os_string();
+        rotated.push(".1");
+        let renamed = std::fs::rename(&path, rotated);
+
+        let mut reopened = Self::open(&path)?;
+        if let Err(error) = renamed {
+            debugln!(
+                "{PLUGIN_NAME} [warn] unable to rotate log file: {error}"
+            );
+            reopened.rotate_at = rotate_at + MAX_LOG_FILE_SIZE;
+        }
+        Ok(reopened)
+    }
+
+    fn write_line(self, line: &str) -> std::io::Result<Self> {
+        let mut log_file = if self.size >= self.rotate_at {
+            self.rotate()?
+        } else {
+            self
+        };
+
+        writeln!(log_file.file, "{line}")?;
+        log_file.size += line.len() as u64 + 1;
+        Ok(log_file)
+    }
+}
+
+/// Set the verbosity and start or stop writing to `file`
+#[cfg(feature = "plugin")]
+pub fn configure(level: Level, file: Option<&Path>) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+
+    let Ok(mut log_file) = LOG_FILE.lock() else {
+        return;
+    };
+    if log_file.as_ref().map(|log_file| log_file.path.as_path()) == file {
+        return;
+    }
+
+    *log_file = None;
+    if let Some(path) = file {
+        match LogFile::open(path) {
+            Ok(opened) => *log_file = Some(opened),
+            Err(error) => debugln!(
+                "{PLUGIN_NAME} [warn] unable to open {}: {error}",
+                path.display()
+            ),
+        }
+    }
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Write an entry to Log.txt and the log file, use the level macros instead
+pub fn write(level: Level, args: fmt::Arguments<'_>) {
+    if !enabled(level) {
+        return;
+    }
+
+    debugln!("{PLUGIN_NAME} [{level}] {args}");
+
+    if let Ok(mut log_file) = LOG_FILE.lock()
+        && let Some(file) = log_file.take()
+    {
+        let line =
+            format!("{} [{level}] {args}", timestamp(SystemTime::now()));
+        match file.write_line(&line) {
+            Ok(file) => *log_file = Some(file),
+            Err(error) => debugln!(
+                "{PLUGIN_NAME} [warn] unable to write log file: {error}"
+            ),
+        }
+    }
+}
+
+/// Record a dataref value a component forced on the simulator
+pub fn override_write(
+    level: Level,
+    component: &str,
+    dataref: &str,
+    old: impl Display,
+    new: impl Display,
+    reason: fmt::Arguments<'_>,
+) {
+    write(
+        level,
+        format_args!(
+            "override component={component} dataref={dataref} old={old} new={new} reason=\"{reason}\""
+        ),
+    );
+}
+
+/// UTC time as `YYYY-MM-DD HH:MM:SS.mmm`
+pub fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{:03}",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{Level, LogFile, MAX_LOG_FILE_SIZE, timestamp};
+
+    #[test]
+    fn levels_parse_and_order() {
+        assert_eq!("warn".parse(), Ok(Level::Warn));
+        assert!("loud".parse::<Level>().is_err());
+        assert!(Level::Error < Level::Trace);
+    }
+
+    #[test]
+    fn timestamps_are_utc() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(timestamp(time), "2024-02-29 12:34:56.789");
+    }
+
+    #[test]
+    fn log_file_rotates() {
+        let dir = std::env::temp_dir()
+            .join(format!("tweaks-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tweaks.log");
+
+        let mut file = LogFile::open(&path).unwrap();
+        file.size = MAX_LOG_FILE_SIZE;
+        file.write_line("after rotation").unwrap();
+
+        assert!(dir.join("tweaks.log.1").exists());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "after rotation\n"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn log_file_keeps_growing_while_rotation_fails() {
+        let dir = std::env::temp_dir()
+            .join(format!("tweaks-log-busy-{}", std::process::id()));
+        // Nothing can be renamed onto a folder which isn't empty
+        std::fs::create_dir_all(dir.join("tweaks.log.1").join("busy"))
+            .unwrap();
+        let path = dir.join("tweaks.log");
+
+        let mut file = LogFile::open(&path).unwrap();
+        file.size = MAX_LOG_FILE_SIZE;
+        let file = file.write_line("rotation failed").unwrap();
+        let file = file.write_line("still logging").unwrap();
+
+        assert_eq!(file.rotate_at, 2 * MAX_LOG_FILE_SIZE);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "rotation failed\nstill logging\n"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
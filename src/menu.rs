@@ -0,0 +1,364 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use xplm::command::CommandHandler;
+use xplm::menu::{ActionItem, CheckHandler, CheckItem, MenuClickHandler};
+use xplm::window::{Window, WindowRef};
+
+use crate::aircraft::Aircraft;
+use crate::profile;
+use crate::recorder::{self, RECORDINGS_DIR, Recorder};
+use crate::registry::ComponentRegistry;
+use crate::settings::{SETTINGS, Settings};
+
+/// One of the plugin's windows
+pub trait Toggle {
+    fn visible(&self) -> bool;
+
+    fn set_visible(&self, visible: bool);
+}
+
+impl Toggle for WindowRef {
+    fn visible(&self) -> bool {
+        Window::visible(self)
+    }
+
+    fn set_visible(&self, visible: bool) {
+        Window::set_visible(self, visible);
+    }
+}
+
+/// Shows or hides one of the plugin's windows
+pub struct WindowToggleHandler {
+    pub window: Rc<dyn Toggle>,
+}
+
+impl WindowToggleHandler {
+    fn toggle(&self) {
+        self.window.set_visible(!self.window.visible());
+    }
+}
+
+impl MenuClickHandler for WindowToggleHandler {
+    fn item_clicked(&mut self, _item: &ActionItem) {
+        self.toggle();
+    }
+}
+
+impl CommandHandler for WindowToggleHandler {
+    fn command_begin(&mut self) {
+        self.toggle();
+    }
+
+    fn command_continue(&mut self) {}
+
+    fn command_end(&mut self) {}
+}
+
+/// Switch throttle sync on or off and remember the choice
+fn set_sync_throttles(enabled: bool) {
+    if let Ok(mut settings) = SETTINGS.lock() {
+        settings.sync_throttles = enabled;
+        settings.save(&Settings::path());
+    }
+}
+
+/// Start or stop recording to the plugin folder.
+/// Returns whether a recording is in progress afterwards.
+fn set_recording(
+    recorder: &RefCell<Recorder>,
+    aircraft: Aircraft,
+    recording: bool,
+) -> bool {
+    let Ok(mut recorder) = recorder.try_borrow_mut() else {
+        return false;
+    };
+    if recorder.is_recording() == recording {
+        return recording;
+    }
+
+    if recording {
+        let Ok((format, header)) = SETTINGS.lock().map(|settings| {
+            (
+                settings.record_format,
+                recorder::header(aircraft, &settings),
+            )
+        }) else {
+            return false;
+        };
+        let dir = Settings::dir().join(RECORDINGS_DIR);
+        match recorder.start_file(&dir, format, &header) {
+            Ok(path) => info!("recording to {}", path.display()),
+            Err(error) => warn!("unable to start recording: {error}"),
+        }
+    } else {
+        recorder.stop();
+        info!("recording stopped");
+    }
+    recorder.is_recording()
+}
+
+/// Switch a component on or off and remember the choice
+fn set_component_enabled(
+    components: &mut ComponentRegistry,
+    id: &str,
+    enabled: bool,
+) {
+    if components.set_enabled(id, enabled) {
+        let state = if enabled { "enabled" } else { "disabled" };
+        info!("component {id} {state}");
+
+        if let Ok(mut settings) = SETTINGS.lock() {
+            settings.set_component_enabled(id, enabled);
+            settings.save(&Settings::path());
+        }
+    }
+}
+
+pub struct SyncThrottlesMenuHandler;
+
+impl CheckHandler for SyncThrottlesMenuHandler {
+    fn item_checked(&mut self, _item: &CheckItem, checked: bool) {
+        set_sync_throttles(checked);
+    }
+}
+
+/// Enables or disables a single component
+pub struct ComponentMenuHandler {
+    pub id: &'static str,
+    pub components: Rc<RefCell<ComponentRegistry>>,
+}
+
+impl CheckHandler for ComponentMenuHandler {
+    fn item_checked(&mut self, _item: &CheckItem, checked: bool) {
+        set_component_enabled(
+            &mut self.components.borrow_mut(),
+            self.id,
+            checked,
+        );
+    }
+}
+
+/// Check items of the plugin menu
+pub struct MenuCheckItems {
+    pub sync_throttles: Rc<CheckItem>,
+
+    /// Enable flag items, by component id
+    pub components: Vec<(&'static str, Rc<CheckItem>)>,
+}
+
+impl MenuCheckItems {
+    /// Check the items which are switched on
+    pub fn refresh(&self, components: &ComponentRegistry) {
+        self.sync_throttles.set_checked(
+            SETTINGS
+                .try_lock()
+                .is_ok_and(|settings| settings.sync_throttles),
+        );
+        for (id, item) in &self.components {
+            if let Some(registered) = components.get(id) {
+                item.set_checked(registered.enabled);
+            }
+        }
+    }
+}
+
+/// Starts or stops recording from the plugin menu
+pub struct RecordingMenuHandler {
+    pub recorder: Rc<RefCell<Recorder>>,
+    pub aircraft: Aircraft,
+}
+
+impl CheckHandler for RecordingMenuHandler {
+    fn item_checked(&mut self, item: &CheckItem, checked: bool) {
+        item.set_checked(set_recording(
+            &self.recorder,
+            self.aircraft,
+            checked,
+        ));
+    }
+}
+
+/// Starts or stops recording like the menu item does
+pub struct RecordingCommandHandler {
+    pub switch: Switch,
+    pub recorder: Rc<RefCell<Recorder>>,
+    pub aircraft: Aircraft,
+    pub item: Rc<CheckItem>,
+}
+
+impl CommandHandler for RecordingCommandHandler {
+    fn command_begin(&mut self) {
+        let current = self
+            .recorder
+            .try_borrow()
+            .is_ok_and(|recorder| recorder.is_recording());
+        let recording = set_recording(
+            &self.recorder,
+            self.aircraft,
+            self.switch.apply(current),
+        );
+        self.item.set_checked(recording);
+    }
+
+    fn command_continue(&mut self) {}
+
+    fn command_end(&mut self) {}
+}
+
+/// Reads the settings file again and applies it
+pub struct ReloadSettingsHandler {
+    pub components: Rc<RefCell<ComponentRegistry>>,
+    pub check_items: Rc<MenuCheckItems>,
+}
+
+impl ReloadSettingsHandler {
+    fn reload(&self) {
+        let settings = Settings::load(&Settings::path());
+
+        let mut components = self.components.borrow_mut();
+        for (id, _) in &self.check_items.components {
+            let Some(info) = components.get(id).map(|comp| comp.info) else {
+                continue;
+            };
+            components.set_enabled(id, settings.component_enabled(&info));
+        }
+
+        settings.configure_log();
+        if let Ok(mut lock) = SETTINGS.lock() {
+            *lock = settings;
+        }
+        self.check_items.refresh(&components);
+        info!("settings reloaded");
+    }
+}
+
+impl MenuClickHandler for ReloadSettingsHandler {
+    fn item_clicked(&mut self, _item: &ActionItem) {
+        self.reload();
+    }
+}
+
+impl CommandHandler for ReloadSettingsHandler {
+    fn command_begin(&mut self) {
+        self.reload();
+    }
+
+    fn command_continue(&mut self) {}
+
+    fn command_end(&mut self) {}
+}
+
+/// What a toggle, on or off command does
+#[derive(Clone, Copy, Debug)]
+pub enum Switch {
+    Toggle,
+    On,
+    Off,
+}
+
+impl Switch {
+    /// Every switch with its command name suffix
+    pub const ALL: [(Self, &'static str); 3] = [
+        (Self::Toggle, "toggle"),
+        (Self::On, "on"),
+        (Self::Off, "off"),
+    ];
+
+    fn apply(self, current: bool) -> bool {
+        match self {
+            Self::Toggle => !current,
+            Self::On => true,
+            Self::Off => false,
+        }
+    }
+}
+
+/// Switches throttle sync like the menu item does
+pub struct SyncThrottlesCommandHandler {
+    pub switch: Switch,
+    pub components: Rc<RefCell<ComponentRegistry>>,
+    pub check_items: Rc<MenuCheckItems>,
+}
+
+impl CommandHandler for SyncThrottlesCommandHandler {
+    fn command_begin(&mut self) {
+        let current = SETTINGS
+            .try_lock()
+            .is_ok_and(|settings| settings.sync_throttles);
+        set_sync_throttles(self.switch.apply(current));
+        self.check_items.refresh(&self.components.borrow());
+    }
+
+    fn command_continue(&mut self) {}
+
+    fn command_end(&mut self) {}
+}
+
+/// Switches a single component like its menu item does
+pub struct ComponentCommandHandler {
+    pub id: &'static str,
+    pub switch: Switch,
+    pub components: Rc<RefCell<ComponentRegistry>>,
+    pub check_items: Rc<MenuCheckItems>,
+}
+
+impl CommandHandler for ComponentCommandHandler {
+    fn command_begin(&mut self) {
+        let mut components = self.components.borrow_mut();
+        let Some(current) = components.get(self.id).map(|comp| comp.enabled)
+        else {
+            return;
+        };
+        set_component_enabled(
+            &mut components,
+            self.id,
+            self.switch.apply(current),
+        );
+        self.check_items.refresh(&components);
+    }
+
+    fn command_continue(&mut self) {}
+
+    fn command_end(&mut self) {}
+}
+
+/// Runs a one-shot action of a component
+pub struct ComponentActionCommandHandler {
+    pub id: &'static str,
+    pub action: &'static str,
+    pub components: Rc<RefCell<ComponentRegistry>>,
+}
+
+impl CommandHandler for ComponentActionCommandHandler {
+    fn command_begin(&mut self) {
+        self.components
+            .borrow_mut()
+            .run_action(self.id, self.action);
+    }
+
+    fn command_continue(&mut self) {}
+
+    fn command_end(&mut self) {}
+}
+
+/// Writes the update profile of every component to the plugin folder
+pub struct ProfileExportHandler {
+    pub components: Rc<RefCell<ComponentRegistry>>,
+}
+
+impl CommandHandler for ProfileExportHandler {
+    fn command_begin(&mut self) {
+        let Ok(components) = self.components.try_borrow() else {
+            return;
+        };
+        match profile::export(&components, &Settings::dir()) {
+            Ok(path) => info!("profile written to {}", path.display()),
+            Err(error) => warn!("unable to write profile: {error}"),
+        }
+    }
+
+    fn command_continue(&mut self) {}
+
+    fn command_end(&mut self) {}
+}
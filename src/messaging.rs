@@ -0,0 +1,199 @@
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::time::Duration;
+
+use crate::registry::{ComponentRegistry, InitState, RegisteredComponent};
+
+/// Ask for the state of a component
+pub const MSG_QUERY_STATE: i32 = 0x1461_0001;
+
+/// Switch a component on, not saved to the settings file
+pub const MSG_ENABLE: i32 = 0x1461_0002;
+
+/// Switch a component off, not saved to the settings file
+pub const MSG_DISABLE: i32 = 0x1461_0003;
+
+/// Pause a component's updates for `seconds`, 0 resumes it
+pub const MSG_SUSPEND: i32 = 0x1461_0004;
+
+/// Sent to every plugin when a component changes state
+pub const MSG_STATE_CHANGED: i32 = 0x1461_0010;
+
+pub const STATE_ENABLED: i32 = 1;
+pub const STATE_READY: i32 = 1 << 1;
+pub const STATE_ACTIVE: i32 = 1 << 2;
+pub const STATE_SUSPENDED: i32 = 1 << 3;
+pub const STATE_FAILED: i32 = 1 << 4;
+
+/// State answered for a component id the plugin doesn't know
+pub const STATE_UNKNOWN: i32 = -1;
+
+/// Message ids other plugins send to this plugin
+const REQUESTS: [i32; 4] =
+    [MSG_QUERY_STATE, MSG_ENABLE, MSG_DISABLE, MSG_SUSPEND];
+
+/// Parameter of every message, as passed by other plugins
+#[repr(C)]
+pub struct ComponentMessage {
+    /// Component id, e.g. `throttle_levers`
+    pub id: *const c_char,
+
+    /// Suspension time, or the time left in a notification
+    pub seconds: f32,
+
+    /// `STATE_*` flags, filled in by this plugin
+    pub state: i32,
+}
+
+/// Something other plugins can ask for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Request {
+    QueryState,
+    Enable,
+    Disable,
+    Suspend(Duration),
+}
+
+impl Request {
+    /// `None` for messages not meant for this plugin
+    pub fn from_message(message: i32, seconds: f32) -> Option<Self> {
+        match message {
+            MSG_QUERY_STATE => Some(Self::QueryState),
+            MSG_ENABLE => Some(Self::Enable),
+            MSG_DISABLE => Some(Self::Disable),
+            MSG_SUSPEND => Some(Self::Suspend(
+                Duration::try_from_secs_f32(seconds).unwrap_or_default(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// `STATE_*` flags of a component
+pub fn state(registered: &RegisteredComponent) -> i32 {
+    let mut state = 0;
+    if registered.enabled {
+        state |= STATE_ENABLED;
+    }
+    match registered.init {
+        InitState::Ready if registered.component.is_active() => {
+            state |= STATE_READY | STATE_ACTIVE;
+        }
+        InitState::Ready => state |= STATE_READY,
+        InitState::Failed { .. } => state |= STATE_FAILED,
+        InitState::Pending { .. } => {}
+    }
+    if !registered.suspended.is_zero() {
+        state |= STATE_SUSPENDED;
+    }
+    state
+}
+
+/// Carry out `request` for component `id`.
+/// Returns the state of the component afterwards.
+pub fn handle(
+    components: &mut ComponentRegistry,
+    request: Request,
+    id: &str,
+) -> i32 {
+    if components.get(id).is_none() {
+        warn!("message for unknown component {id:?}");
+        return STATE_UNKNOWN;
+    }
+
+    match request {
+        Request::QueryState => {}
+        Request::Enable | Request::Disable => {
+            let enabled = request == Request::Enable;
+            if components
+                .get(id)
+                .is_some_and(|comp| comp.enabled != enabled)
+            {
+                components.set_enabled(id, enabled);
+                let state = if enabled { "enabled" } else { "disabled" };
+                info!("component {id} {state} by another plugin");
+            }
+        }
+        Request::Suspend(duration) => {
+            components.suspend(id, duration);
+        }
+    }
+
+    components.get(id).map_or(STATE_UNKNOWN, state)
+}
+
+/// Handle an XPLM message, ignoring those meant for other plugins.
+/// Only the `param` of this plugin's messages is looked at.
+///
+/// # Safety
+/// For the `MSG_*` ids of this plugin, `param` must be null or point
+/// to a [`ComponentMessage`] whose `id` is null or a NUL-terminated string.
+pub unsafe fn receive(
+    components: &mut ComponentRegistry,
+    message: i32,
+    param: *mut c_void,
+) {
+    // X-Plane's own messages carry integers in `param`, not pointers
+    if !REQUESTS.contains(&message) {
+        return;
+    }
+    // SAFETY: guaranteed by the caller for this plugin's messages
+    let Some(param) = (unsafe { param.cast::<ComponentMessage>().as_mut() })
+    else {
+        return;
+    };
+    let Some(request) = Request::from_message(message, param.seconds) else {
+        return;
+    };
+    if param.id.is_null() {
+        param.state = STATE_UNKNOWN;
+        return;
+    }
+
+    // SAFETY: guaranteed by the caller
+    let id = unsafe { CStr::from_ptr(param.id) };
+    param.state = handle(components, request, &id.to_string_lossy());
+}
+
+/// Tells other plugins about state changes
+pub trait Notifier {
+    fn state_changed(&self, id: &str, state: i32, suspended: Duration);
+}
+
+/// Answer a message of this plugin with [`STATE_UNKNOWN`]
+/// when its components can't be reached
+///
+/// # Safety
+/// Same as [`receive`]
+pub unsafe fn reject(message: i32, param: *mut c_void) {
+    if !REQUESTS.contains(&message) {
+        return;
+    }
+    // SAFETY: guaranteed by the caller for this plugin's messages
+    if let Some(param) = unsafe { param.cast::<ComponentMessage>().as_mut() } {
+        param.state = STATE_UNKNOWN;
+    }
+}
+
+/// Broadcasts [`MSG_STATE_CHANGED`] through X-Plane
+pub struct XPlaneNotifier;
+
+impl Notifier for XPlaneNotifier {
+    fn state_changed(&self, id: &str, state: i32, suspended: Duration) {
+        let Ok(id) = CString::new(id) else {
+            return;
+        };
+        let mut message = ComponentMessage {
+            id: id.as_ptr(),
+            seconds: suspended.as_secs_f32(),
+            state,
+        };
+        // SAFETY: the message outlives the call, receivers must copy it
+        unsafe {
+            xplm_sys::XPLMSendMessageToPlugin(
+                xplm_sys::XPLM_NO_PLUGIN_ID,
+                MSG_STATE_CHANGED,
+                (&raw mut message).cast(),
+            );
+        }
+    }
+}
@@ -1,78 +1,111 @@
-use xplm::data::borrowed::DataRef;
-use xplm::data::{DataRead, DataReadWrite, ReadWrite};
-use xplm::debugln;
+use crate::binding::{Bind, ReadF32, WriteI32};
+use crate::component::{
+    ComponentInfo, PluginComponent, SavedValue, UpdateRate,
+};
+use crate::log::{self, Level};
+use crate::settings::tunables;
 
-use crate::component::PluginComponent;
-use crate::plugin::PLUGIN_NAME;
-use crate::plugin::PluginError;
+const ID: &str = "nosewheel_steering";
 
 /// UFMC sometimes blocks nosewheel steering...
 /// This enables nosewheel steering as long as there is enough pressure
 /// in the green system.
 pub struct NosewheelSteering {
-    is_initialized: bool,
+    hydraulic_pressure_green: ReadF32,
+    override_wheel_steer: WriteI32,
+    saved_override_wheel_steer: SavedValue<i32>,
 
-    /// `sim/cockpit2/hydraulics/indicators/hydraulic_pressure_2`
-    hydraulic_pressure_green: Option<DataRef<f32>>,
-
-    /// `sim/operation/override/override_wheel_steer`
-    override_wheel_steer: Option<DataRef<i32, ReadWrite>>,
+    /// Override value wanted by the last update
+    wanted_override: Option<i32>,
 }
 
 impl NosewheelSteering {
     pub fn new() -> Self {
         Self {
-            is_initialized: false,
-
-            hydraulic_pressure_green: None,
-            override_wheel_steer: None,
+            hydraulic_pressure_green: ReadF32::required(
+                "sim/cockpit2/hydraulics/indicators/hydraulic_pressure_2",
+            ),
+            override_wheel_steer: WriteI32::required(
+                "sim/operation/override/override_wheel_steer",
+            ),
+            saved_override_wheel_steer: SavedValue::new(),
+            wanted_override: None,
         }
     }
 
-    fn initialize(&mut self) -> Result<(), PluginError> {
-        if self.hydraulic_pressure_green.is_none() {
-            self.hydraulic_pressure_green = Some(DataRef::find(
-                "sim/cockpit2/hydraulics/indicators/hydraulic_pressure_2",
-            )?);
-        }
+    /// Override value for the current green system pressure
+    fn override_wanted(&self) -> i32 {
+        let pressure = self.hydraulic_pressure_green.get();
+        i32::from(pressure > tunables().nosewheel_min_pressure)
+    }
+}
 
-        if self.override_wheel_steer.is_none() {
-            self.override_wheel_steer = Some(
-                DataRef::find("sim/operation/override/override_wheel_steer")?
-                    .writeable()?,
-            );
+impl PluginComponent for NosewheelSteering {
+    fn info(&self) -> ComponentInfo {
+        ComponentInfo {
+            id: ID,
+            name: "Fix nosewheel steering",
+            description: "Enable nosewheel steering with UFMC while the green system is pressurized",
+            enabled_by_default: true,
         }
+    }
 
-        Ok(())
+    fn update_rate(&self) -> UpdateRate {
+        UpdateRate::OnChange
     }
-}
 
-impl PluginComponent for NosewheelSteering {
-    fn is_initialized(&self) -> bool {
-        self.is_initialized
+    /// Pressure crossed the threshold or UFMC changed the override
+    fn inputs_changed(&mut self) -> bool {
+        self.wanted_override != Some(self.override_wanted())
+            || self.wanted_override != self.override_wheel_steer.value()
+    }
+
+    fn bindings(&mut self, visit: &mut dyn FnMut(&mut dyn Bind)) {
+        visit(&mut self.hydraulic_pressure_green);
+        visit(&mut self.override_wheel_steer);
     }
 
     fn update(&mut self) {
-        if !self.is_initialized {
-            if self.initialize().is_ok() {
-                self.is_initialized = true;
-                debugln!(
-                    "{PLUGIN_NAME} FixNosewheelSteering component initialized"
-                );
-            } else {
-                return;
-            }
+        let wanted = self.override_wanted();
+        let pressure = self.hydraulic_pressure_green.get();
+        let old = self.override_wheel_steer.get();
+        self.saved_override_wheel_steer.save(old);
+
+        if self.override_wheel_steer.set(wanted) {
+            log::override_write(
+                Level::Info,
+                ID,
+                self.override_wheel_steer.name(),
+                old,
+                wanted,
+                format_args!(
+                    "green pressure {pressure} psi, steering needs more than {} psi",
+                    tunables().nosewheel_min_pressure
+                ),
+            );
         }
+        self.wanted_override = Some(wanted);
+    }
 
-        if let Some(override_wheel_steer) = self.override_wheel_steer.as_mut()
-            && let Some(hydraulic_pressure_green) =
-                self.hydraulic_pressure_green.as_ref()
+    fn is_active(&self) -> bool {
+        self.wanted_override == Some(1)
+    }
+
+    #[cfg(feature = "plugin")]
+    fn teardown(&mut self) {
+        if let Some(old) = self.override_wheel_steer.value()
+            && let Some(value) = self.saved_override_wheel_steer.take()
         {
-            if hydraulic_pressure_green.get() > 100.0 {
-                override_wheel_steer.set(1);
-            } else {
-                override_wheel_steer.set(0);
-            }
+            self.override_wheel_steer.set(value);
+            self.wanted_override = None;
+            log::override_write(
+                Level::Info,
+                ID,
+                self.override_wheel_steer.name(),
+                old,
+                value,
+                format_args!("restore value from before the plugin"),
+            );
         }
     }
 }
@@ -1,86 +1,11 @@
 use std::ffi::NulError;
-use std::sync::Mutex;
-
-use xplm::data::StringRead;
-use xplm::data::borrowed::DataRef;
-use xplm::debugln;
-use xplm::flight_loop::FlightLoop;
-use xplm::menu::{CheckItem, Menu};
-use xplm::plugin::management::plugin_with_signature;
-use xplm::plugin::{Plugin, PluginInfo};
-
-use crate::handler::{FlightLoopHandler, SyncThrottlesMenuHandler};
 
 pub static PLUGIN_NAME: &str =
     concat!("BAe 146 Tweaks", " v", env!("CARGO_PKG_VERSION"));
-static PLUGIN_SIGNATURE: &str =
-    concat!("io.github.telephono.", env!("CARGO_PKG_NAME"));
-static PLUGIN_DESCRIPTION: &str = "BAe 146 fixes and tweaks";
-
-pub static SYNC_THROTTLES: Mutex<bool> = Mutex::new(true);
-
-pub struct TweaksPlugin {
-    flight_loop: FlightLoop,
-    _plugin_menu: Menu,
-}
-
-impl Plugin for TweaksPlugin {
-    type Error = PluginError;
-
-    fn start() -> Result<Self, Self::Error> {
-        if plugin_with_signature(PLUGIN_SIGNATURE).is_some() {
-            return Err(PluginError::AlreadyRunning);
-        }
-
-        let acf_icao: DataRef<[u8]> =
-            DataRef::find("sim/aircraft/view/acf_ICAO")?;
-        let acf_icao = acf_icao.get_as_string()?;
-        match acf_icao.as_str() {
-            "B461" | "B462" | "B463" => {
-                debugln!("{PLUGIN_NAME} starting up...");
-            }
-            _ => return Err(PluginError::AircraftNotSupported(acf_icao)),
-        }
-
-        let sync_throttles = SYNC_THROTTLES.try_lock().is_ok_and(|lock| *lock);
-        let plugin_menu = Menu::new("BAe 146 Tweaks")?;
-        plugin_menu.add_child(CheckItem::new(
-            "Sync throttles",
-            sync_throttles,
-            SyncThrottlesMenuHandler,
-        )?);
-        plugin_menu.add_to_plugins_menu();
-
-        let handler = FlightLoopHandler::new();
-
-        let plugin = Self {
-            flight_loop: FlightLoop::new(handler),
-            _plugin_menu: plugin_menu,
-        };
 
-        debugln!("{PLUGIN_NAME} startup complete");
-        Ok(plugin)
-    }
-
-    fn enable(&mut self) -> Result<(), Self::Error> {
-        self.flight_loop.schedule_after_loops(300);
-        debugln!("{PLUGIN_NAME} enabled");
-        Ok(())
-    }
-
-    fn disable(&mut self) {
-        self.flight_loop.deactivate();
-        debugln!("{PLUGIN_NAME} disabled");
-    }
-
-    fn info(&self) -> PluginInfo {
-        PluginInfo {
-            name: PLUGIN_NAME.to_string(),
-            signature: PLUGIN_SIGNATURE.to_string(),
-            description: PLUGIN_DESCRIPTION.to_string(),
-        }
-    }
-}
+/// Prefix of the datarefs and commands owned by this plugin
+#[cfg(feature = "plugin")]
+pub static PLUGIN_PREFIX: &str = "telephono/tweaks";
 
 #[derive(Debug, thiserror::Error)]
 pub enum PluginError {
@@ -96,9 +21,27 @@ pub enum PluginError {
     #[error("Command not found: {0}")]
     CommandFindError(#[from] xplm::command::CommandFindError),
 
+    #[error("Command could not be created: {0}")]
+    CommandCreateError(#[from] xplm::command::CommandCreateError),
+
     #[error("DataRef not found: {0}")]
-    DataRefFindError(#[from] xplm::data::borrowed::FindError),
+    DataRefNotFound(String),
+
+    #[error("DataRef not writeable: {0}")]
+    DataRefNotWriteable(String),
+
+    #[error("DataRef could not be created: {0}")]
+    DataRefNotCreated(String),
 
     #[error(transparent)]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid recording: {0}")]
+    InvalidRecording(String),
+
+    #[error("Invalid setting in line {line}: {reason}")]
+    InvalidSetting { line: usize, reason: String },
 }
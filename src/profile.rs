@@ -0,0 +1,371 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::ops::Sub;
+use std::time::Duration;
+#[cfg(feature = "plugin")]
+use std::{
+    cell::Cell,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+#[cfg(feature = "plugin")]
+use crate::dataref::{
+    ArrayRead, ArrayReadWrite, DataRefProvider, ScalarRead, ScalarReadWrite,
+    StringRead,
+};
+#[cfg(feature = "plugin")]
+use crate::log;
+#[cfg(feature = "plugin")]
+use crate::plugin::PluginError;
+#[cfg(feature = "plugin")]
+use crate::registry::ComponentRegistry;
+
+/// Most recent update durations kept for the 99th percentile
+const RECENT_UPDATES: usize = 1000;
+
+/// Column names of an exported profile, durations in microseconds
+#[cfg(feature = "plugin")]
+const CSV_COLUMNS: &str =
+    "component,updates,min_us,avg_us,p99_us,max_us,reads,writes";
+
+/// Dataref accesses counted by [`CountingDataRefs`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Accesses {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+impl Sub for Accesses {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            reads: self.reads - other.reads,
+            writes: self.writes - other.writes,
+        }
+    }
+}
+
+/// Durations and dataref accesses of a component's updates
+#[derive(Debug, Default)]
+pub struct UpdateStats {
+    pub updates: u64,
+    pub min: Duration,
+    pub max: Duration,
+    total: Duration,
+    recent: VecDeque<Duration>,
+    pub accesses: Accesses,
+}
+
+impl UpdateStats {
+    pub fn record(&mut self, duration: Duration, accesses: Accesses) {
+        self.min = if self.updates == 0 {
+            duration
+        } else {
+            self.min.min(duration)
+        };
+        self.max = self.max.max(duration);
+        self.updates += 1;
+        self.total += duration;
+
+        if self.recent.len() == RECENT_UPDATES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(duration);
+
+        self.accesses.reads += accesses.reads;
+        self.accesses.writes += accesses.writes;
+    }
+
+    pub fn average(&self) -> Duration {
+        let nanos = self
+            .total
+            .as_nanos()
+            .checked_div(u128::from(self.updates))
+            .unwrap_or_default();
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    /// 99th percentile of the most recent updates
+    pub fn p99(&self) -> Duration {
+        let mut recent = Vec::from(self.recent.clone());
+        recent.sort_unstable();
+        let rank = (recent.len() * 99).div_ceil(100);
+        rank.checked_sub(1)
+            .and_then(|index| recent.get(index))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl Display for UpdateStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} updates, min {:?}, avg {:?}, p99 {:?}, max {:?}, \
+             {} reads, {} writes",
+            self.updates,
+            self.min,
+            self.average(),
+            self.p99(),
+            self.max,
+            self.accesses.reads,
+            self.accesses.writes
+        )
+    }
+}
+
+/// Profile of every component as CSV
+#[cfg(feature = "plugin")]
+pub fn write_csv(
+    components: &ComponentRegistry,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(out, "{CSV_COLUMNS}")?;
+    for registered in components.iter() {
+        let stats = &registered.profile;
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            registered.info.id,
+            stats.updates,
+            stats.min.as_micros(),
+            stats.average().as_micros(),
+            stats.p99().as_micros(),
+            stats.max.as_micros(),
+            stats.accesses.reads,
+            stats.accesses.writes
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the profile to a new file in `dir`, returns its path
+#[cfg(feature = "plugin")]
+pub fn export(
+    components: &ComponentRegistry,
+    dir: &Path,
+) -> Result<PathBuf, PluginError> {
+    fs::create_dir_all(dir)?;
+    let stamp = log::timestamp(SystemTime::now());
+    let name = format!("profile-{}.csv", stamp[..19].replace([' ', ':'], "-"));
+    let path = dir.join(name);
+    let mut file = BufWriter::new(File::create(&path)?);
+    write_csv(components, &mut file)?;
+    file.flush()?;
+    Ok(path)
+}
+
+/// Dataref counting its reads and writes
+#[cfg(feature = "plugin")]
+struct Counted<D: ?Sized> {
+    dataref: Box<D>,
+    accesses: Rc<Cell<Accesses>>,
+}
+
+#[cfg(feature = "plugin")]
+impl<D: ?Sized> Counted<D> {
+    fn count(&self, reads: u64, writes: u64) {
+        let mut accesses = self.accesses.get();
+        accesses.reads += reads;
+        accesses.writes += writes;
+        self.accesses.set(accesses);
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<T> ScalarRead<T> for Counted<dyn ScalarRead<T>> {
+    fn get(&self) -> T {
+        self.count(1, 0);
+        self.dataref.get()
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<T> ScalarRead<T> for Counted<dyn ScalarReadWrite<T>> {
+    fn get(&self) -> T {
+        self.count(1, 0);
+        self.dataref.get()
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<T> ScalarReadWrite<T> for Counted<dyn ScalarReadWrite<T>> {
+    fn set(&mut self, value: T) {
+        self.count(0, 1);
+        self.dataref.set(value);
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl ArrayRead<f32> for Counted<dyn ArrayRead<f32>> {
+    fn get(&self, dest: &mut [f32]) -> usize {
+        self.count(1, 0);
+        self.dataref.get(dest)
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl ArrayRead<f32> for Counted<dyn ArrayReadWrite<f32>> {
+    fn get(&self, dest: &mut [f32]) -> usize {
+        self.count(1, 0);
+        self.dataref.get(dest)
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl ArrayReadWrite<f32> for Counted<dyn ArrayReadWrite<f32>> {
+    fn set(&mut self, values: &[f32]) {
+        self.count(0, 1);
+        self.dataref.set(values);
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl StringRead for Counted<dyn StringRead> {
+    fn get_as_string(&self) -> Result<String, PluginError> {
+        self.count(1, 0);
+        self.dataref.get_as_string()
+    }
+}
+
+/// Looks up datarefs in `data`, counting every access to found datarefs
+#[cfg(feature = "plugin")]
+pub struct CountingDataRefs<D> {
+    data: D,
+    accesses: Rc<Cell<Accesses>>,
+}
+
+#[cfg(feature = "plugin")]
+impl<D> CountingDataRefs<D> {
+    pub const fn new(data: D, accesses: Rc<Cell<Accesses>>) -> Self {
+        Self { data, accesses }
+    }
+
+    fn counted<T: ?Sized>(&self, dataref: Box<T>) -> Counted<T> {
+        Counted {
+            dataref,
+            accesses: Rc::clone(&self.accesses),
+        }
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<D: DataRefProvider> DataRefProvider for CountingDataRefs<D> {
+    fn find_i32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<i32>>, PluginError> {
+        Ok(Box::new(self.counted(self.data.find_i32(name)?)))
+    }
+
+    fn find_i32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        Ok(Box::new(self.counted(self.data.find_i32_writeable(name)?)))
+    }
+
+    fn find_f32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<f32>>, PluginError> {
+        Ok(Box::new(self.counted(self.data.find_f32(name)?)))
+    }
+
+    fn find_f32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        Ok(Box::new(self.counted(self.data.find_f32_writeable(name)?)))
+    }
+
+    fn find_f32_array(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayRead<f32>>, PluginError> {
+        Ok(Box::new(self.counted(self.data.find_f32_array(name)?)))
+    }
+
+    fn find_f32_array_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayReadWrite<f32>>, PluginError> {
+        Ok(Box::new(
+            self.counted(self.data.find_f32_array_writeable(name)?),
+        ))
+    }
+
+    fn find_string(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn StringRead>, PluginError> {
+        Ok(Box::new(self.counted(self.data.find_string(name)?)))
+    }
+
+    fn create_i32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        self.data.create_i32(name, writeable)
+    }
+
+    fn create_f32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        self.data.create_f32(name, writeable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Accesses, UpdateStats};
+
+    #[test]
+    fn stats_follow_the_recorded_updates() {
+        let mut stats = UpdateStats::default();
+        assert_eq!(stats.average(), Duration::ZERO);
+        assert_eq!(stats.p99(), Duration::ZERO);
+
+        for micros in 1..=200 {
+            stats.record(
+                Duration::from_micros(micros),
+                Accesses {
+                    reads: 2,
+                    writes: 1,
+                },
+            );
+        }
+        assert_eq!(stats.min, Duration::from_micros(1));
+        assert_eq!(stats.max, Duration::from_micros(200));
+        assert_eq!(stats.average(), Duration::from_nanos(100_500));
+        assert_eq!(stats.p99(), Duration::from_micros(198));
+        assert_eq!(
+            stats.accesses,
+            Accesses {
+                reads: 400,
+                writes: 200
+            }
+        );
+    }
+
+    #[test]
+    fn p99_only_looks_at_recent_updates() {
+        let mut stats = UpdateStats::default();
+        stats.record(Duration::from_secs(1), Accesses::default());
+        for _ in 0..super::RECENT_UPDATES {
+            stats.record(Duration::from_micros(5), Accesses::default());
+        }
+        assert_eq!(stats.p99(), Duration::from_micros(5));
+        assert_eq!(stats.max, Duration::from_secs(1));
+    }
+}
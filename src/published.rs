@@ -0,0 +1,298 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::dataref::{DataRefProvider, ScalarReadWrite};
+use crate::plugin::{PLUGIN_PREFIX, PluginError};
+use crate::registry::{ComponentRegistry, InitState};
+use crate::settings::{
+    GPU_VOLTS, HSI_RNAV_SELECTOR, INIT_TIMEOUT_SECS, NOSEWHEEL_MIN_PRESSURE,
+    RADIO_MIN_BUS_VOLTS, SETTINGS, STARTUP_DELAY_LOOPS, Tunable, tunables,
+};
+
+/// Owned dataref mirroring a value of the plugin.
+/// Remembers the last published value
+/// to tell writes by other plugins apart.
+struct Mirror<T> {
+    dataref: Box<dyn ScalarReadWrite<T>>,
+    published: T,
+}
+
+impl<T: Copy + PartialEq> Mirror<T> {
+    fn new(mut dataref: Box<dyn ScalarReadWrite<T>>, value: T) -> Self {
+        dataref.set(value);
+        Self {
+            dataref,
+            published: value,
+        }
+    }
+
+    /// Value written by somebody else since the last publish
+    fn written(&self) -> Option<T> {
+        let value = self.dataref.get();
+        (value != self.published).then_some(value)
+    }
+
+    fn publish(&mut self, value: T) {
+        if self.dataref.get() != value {
+            self.dataref.set(value);
+        }
+        self.published = value;
+    }
+}
+
+/// `telephono/tweaks/<id>/...`
+struct ComponentDataRefs {
+    id: &'static str,
+
+    /// 1 while the component runs, writeable
+    enabled: Mirror<i32>,
+
+    /// 1 once the component found its datarefs
+    initialized: Mirror<i32>,
+
+    /// 1 while the component overrides the simulator
+    active: Mirror<i32>,
+}
+
+/// Datarefs other plugins, SASL scripts and cockpit builders can use
+/// to watch and control the tweaks.
+/// Writes to them change the running plugin but are not saved.
+pub struct PublishedDataRefs {
+    /// `telephono/tweaks/sync_throttles`, writeable
+    sync_throttles: Mirror<i32>,
+
+    components: Vec<ComponentDataRefs>,
+
+    /// `telephono/tweaks/settings/...`, all writeable
+    gpu_volts: Mirror<f32>,
+    radio_min_bus_volts: Mirror<f32>,
+    nosewheel_min_pressure: Mirror<f32>,
+    hsi_rnav_selector: Mirror<i32>,
+    startup_delay_loops: Mirror<i32>,
+    init_timeout_secs: Mirror<f32>,
+
+    /// Writes to the settings datarefs wait for the next frame
+    /// while somebody else holds the settings
+    settings_pending: bool,
+}
+
+impl PublishedDataRefs {
+    /// Create all datarefs, holding the current state and settings
+    pub fn new(
+        data: &dyn DataRefProvider,
+        components: &ComponentRegistry,
+    ) -> Result<Self, PluginError> {
+        let sync_throttles = SETTINGS
+            .try_lock()
+            .is_ok_and(|settings| settings.sync_throttles);
+        let tunables = tunables();
+
+        let int_dataref = |name: &str, writeable: bool| {
+            data.create_i32(&format!("{PLUGIN_PREFIX}/{name}"), writeable)
+        };
+        let float_dataref = |name: &str| {
+            data.create_f32(&format!("{PLUGIN_PREFIX}/{name}"), true)
+        };
+        let setting = |key: &str| format!("settings/{key}");
+
+        let published = Self {
+            sync_throttles: Mirror::new(
+                int_dataref("sync_throttles", true)?,
+                i32::from(sync_throttles),
+            ),
+            components: components
+                .iter()
+                .map(|registered| {
+                    let id = registered.info.id;
+                    Ok(ComponentDataRefs {
+                        id,
+                        enabled: Mirror::new(
+                            int_dataref(&format!("{id}/enabled"), true)?,
+                            i32::from(registered.enabled),
+                        ),
+                        initialized: Mirror::new(
+                            int_dataref(&format!("{id}/initialized"), false)?,
+                            0,
+                        ),
+                        active: Mirror::new(
+                            int_dataref(&format!("{id}/active"), false)?,
+                            0,
+                        ),
+                    })
+                })
+                .collect::<Result<_, PluginError>>()?,
+            gpu_volts: Mirror::new(
+                float_dataref(&setting(GPU_VOLTS.key))?,
+                tunables.gpu_volts,
+            ),
+            radio_min_bus_volts: Mirror::new(
+                float_dataref(&setting(RADIO_MIN_BUS_VOLTS.key))?,
+                tunables.radio_min_bus_volts,
+            ),
+            nosewheel_min_pressure: Mirror::new(
+                float_dataref(&setting(NOSEWHEEL_MIN_PRESSURE.key))?,
+                tunables.nosewheel_min_pressure,
+            ),
+            hsi_rnav_selector: Mirror::new(
+                int_dataref(&setting(HSI_RNAV_SELECTOR.key), true)?,
+                tunables.hsi_rnav_selector,
+            ),
+            startup_delay_loops: Mirror::new(
+                int_dataref(&setting(STARTUP_DELAY_LOOPS.key), true)?,
+                i32::try_from(tunables.startup_delay_loops)
+                    .unwrap_or(i32::MAX),
+            ),
+            init_timeout_secs: Mirror::new(
+                float_dataref(&setting(INIT_TIMEOUT_SECS.key))?,
+                tunables.init_timeout_secs,
+            ),
+            settings_pending: false,
+        };
+
+        debug!("published datarefs under {PLUGIN_PREFIX}");
+        Ok(published)
+    }
+
+    /// Apply values other plugins wrote to the writeable datarefs.
+    /// Returns `true` if anything changed.
+    pub fn apply_writes(
+        &mut self,
+        components: &mut ComponentRegistry,
+    ) -> bool {
+        let mut changed = false;
+        for refs in &self.components {
+            let Some(enabled) = refs.enabled.written().map(|value| value != 0)
+            else {
+                continue;
+            };
+            // Only the running component, the settings file is the user's
+            if components
+                .get(refs.id)
+                .is_none_or(|registered| registered.enabled == enabled)
+            {
+                continue;
+            }
+            components.set_enabled(refs.id, enabled);
+            info!(
+                "component {} {} by dataref",
+                refs.id,
+                if enabled { "enabled" } else { "disabled" }
+            );
+            changed = true;
+        }
+
+        let Ok(mut settings) = SETTINGS.try_lock() else {
+            self.settings_pending = true;
+            return changed;
+        };
+        self.settings_pending = false;
+
+        if let Some(sync_throttles) = self.sync_throttles.written() {
+            settings.sync_throttles = sync_throttles != 0;
+            info!(
+                "sync_throttles set to {} by dataref",
+                settings.sync_throttles
+            );
+            changed = true;
+        }
+
+        let tunables = &mut settings.tunables;
+        changed |= apply_tunable(
+            self.gpu_volts.written(),
+            &GPU_VOLTS,
+            &mut tunables.gpu_volts,
+        );
+        changed |= apply_tunable(
+            self.radio_min_bus_volts.written(),
+            &RADIO_MIN_BUS_VOLTS,
+            &mut tunables.radio_min_bus_volts,
+        );
+        changed |= apply_tunable(
+            self.nosewheel_min_pressure.written(),
+            &NOSEWHEEL_MIN_PRESSURE,
+            &mut tunables.nosewheel_min_pressure,
+        );
+        changed |= apply_tunable(
+            self.hsi_rnav_selector.written(),
+            &HSI_RNAV_SELECTOR,
+            &mut tunables.hsi_rnav_selector,
+        );
+        changed |= apply_tunable(
+            // Negative values are out of range as well
+            self.startup_delay_loops
+                .written()
+                .map(|loops| u32::try_from(loops).unwrap_or(0)),
+            &STARTUP_DELAY_LOOPS,
+            &mut tunables.startup_delay_loops,
+        );
+        changed |= apply_tunable(
+            self.init_timeout_secs.written(),
+            &INIT_TIMEOUT_SECS,
+            &mut tunables.init_timeout_secs,
+        );
+
+        changed
+    }
+
+    /// Update the datarefs to the current state and settings.
+    /// Also reverts rejected writes, but keeps those still pending.
+    pub fn publish(&mut self, components: &ComponentRegistry) {
+        for refs in &mut self.components {
+            let Some(registered) = components.get(refs.id) else {
+                continue;
+            };
+            let ready =
+                registered.enabled && registered.init == InitState::Ready;
+
+            refs.enabled.publish(i32::from(registered.enabled));
+            refs.initialized.publish(i32::from(ready));
+            refs.active
+                .publish(i32::from(ready && registered.component.is_active()));
+        }
+
+        if self.settings_pending {
+            return;
+        }
+        let Ok(settings) = SETTINGS.try_lock() else {
+            return;
+        };
+        let tunables = &settings.tunables;
+
+        self.sync_throttles
+            .publish(i32::from(settings.sync_throttles));
+        self.gpu_volts.publish(tunables.gpu_volts);
+        self.radio_min_bus_volts
+            .publish(tunables.radio_min_bus_volts);
+        self.nosewheel_min_pressure
+            .publish(tunables.nosewheel_min_pressure);
+        self.hsi_rnav_selector.publish(tunables.hsi_rnav_selector);
+        self.startup_delay_loops.publish(
+            i32::try_from(tunables.startup_delay_loops).unwrap_or(i32::MAX),
+        );
+        self.init_timeout_secs.publish(tunables.init_timeout_secs);
+    }
+}
+
+/// Apply a value written to a tunable dataref if it is in range.
+/// Returns `true` if `target` changed.
+fn apply_tunable<T: Copy + PartialOrd + FromStr + Display>(
+    written: Option<T>,
+    tunable: &Tunable<T>,
+    target: &mut T,
+) -> bool {
+    let Some(value) = written else {
+        return false;
+    };
+
+    match tunable.check(value) {
+        Ok(value) => {
+            *target = value;
+            info!("{} set to {value} by dataref", tunable.key);
+            true
+        }
+        Err(reason) => {
+            warn!("ignoring dataref write, {reason}");
+            false
+        }
+    }
+}
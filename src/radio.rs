@@ -1,161 +1,173 @@
-use xplm::data::borrowed::DataRef;
-use xplm::data::{ArrayRead, DataRead, DataReadWrite, ReadWrite};
-use xplm::debugln;
-
-use crate::component::PluginComponent;
-use crate::plugin::PLUGIN_NAME;
+use crate::aircraft::Aircraft;
+use crate::binding::{Bind, ReadF32Array, ReadI32, WriteI32};
+use crate::component::{ComponentInfo, PluginComponent, UpdateRate};
+use crate::log::{self, Level};
 use crate::plugin::PluginError;
+use crate::settings::tunables;
+
+const ID: &str = "radio";
 
 /// Fix radio power based on bus voltage available
 #[allow(clippy::struct_field_names)]
 pub struct Radio {
-    is_initialized: bool,
-
-    /// `sim/cockpit2/electrical/bus_volts`
-    bus_volts: Option<DataRef<[f32]>>,
-    bus_volts_slice: [f32; 2],
-
-    /// `sim/cockpit2/radios/actuators/gps_power`
-    radio_gps1_power: Option<DataRef<i32>>,
-
-    /// `sim/cockpit2/radios/actuators/gps2_power`
-    radio_gps2_power: Option<DataRef<i32>>,
-
-    /// `sim/cockpit2/radios/actuators/com1_power`
-    radio_com1_power: Option<DataRef<i32, ReadWrite>>,
-
-    /// `sim/cockpit2/radios/actuators/com2_power`
-    radio_com2_power: Option<DataRef<i32, ReadWrite>>,
-
-    /// `thranda/generic/com1/genCom1Pwr`
-    thranda_radio_com1_power: Option<DataRef<i32>>,
-
-    /// `thranda/generic/com1/genCom2Pwr` [sic!]
-    thranda_radio_com2_power: Option<DataRef<i32>>,
+    /// Indices into `bus_volts` of the buses powering COM1 and COM2,
+    /// set for the variant on initialization
+    com_buses: [usize; 2],
+
+    bus_volts: ReadF32Array,
+    bus_volts_slice: [f32; 6],
+
+    radio_gps1_power: ReadI32,
+    radio_gps2_power: ReadI32,
+    radio_com1_power: WriteI32,
+    radio_com2_power: WriteI32,
+    thranda_radio_com1_power: ReadI32,
+    thranda_radio_com2_power: ReadI32,
 }
 
 impl Radio {
     pub fn new() -> Self {
         Self {
-            is_initialized: false,
-
-            bus_volts: None,
-            bus_volts_slice: [0.0; 2],
-            radio_gps1_power: None,
-            radio_gps2_power: None,
-            radio_com1_power: None,
-            radio_com2_power: None,
-            thranda_radio_com1_power: None,
-            thranda_radio_com2_power: None,
-        }
-    }
-
-    /// Fetch SASL datarefs if they are available
-    fn initialize(&mut self) -> Result<(), PluginError> {
-        if self.bus_volts.is_none() {
-            self.bus_volts =
-                Some(DataRef::find("sim/cockpit2/electrical/bus_volts")?);
-        }
-
-        if self.radio_gps1_power.is_none() {
-            self.radio_gps1_power = Some(DataRef::find(
+            com_buses: [0, 1],
+            bus_volts: ReadF32Array::required(
+                "sim/cockpit2/electrical/bus_volts",
+            ),
+            bus_volts_slice: [0.0; 6],
+            radio_gps1_power: ReadI32::required(
                 "sim/cockpit2/radios/actuators/gps_power",
-            )?);
-        }
-
-        if self.radio_gps2_power.is_none() {
-            self.radio_gps2_power = Some(DataRef::find(
+            ),
+            radio_gps2_power: ReadI32::required(
                 "sim/cockpit2/radios/actuators/gps2_power",
-            )?);
-        }
-
-        if self.radio_com1_power.is_none() {
-            self.radio_com1_power = Some(
-                DataRef::find("sim/cockpit2/radios/actuators/com1_power")?
-                    .writeable()?,
-            );
+            ),
+            radio_com1_power: WriteI32::required(
+                "sim/cockpit2/radios/actuators/com1_power",
+            ),
+            radio_com2_power: WriteI32::required(
+                "sim/cockpit2/radios/actuators/com2_power",
+            ),
+            thranda_radio_com1_power: ReadI32::required(
+                "thranda/generic/com1/genCom1Pwr",
+            ),
+            // [sic!]
+            thranda_radio_com2_power: ReadI32::required(
+                "thranda/generic/com1/genCom2Pwr",
+            ),
         }
+    }
 
-        if self.radio_com2_power.is_none() {
-            self.radio_com2_power = Some(
-                DataRef::find("sim/cockpit2/radios/actuators/com2_power")?
-                    .writeable()?,
-            );
-        }
+    /// Voltage of the buses powering COM1 and COM2
+    fn com_bus_volts(&self) -> [f32; 2] {
+        self.com_buses.map(|bus| self.bus_volts_slice[bus])
+    }
+}
 
-        if self.thranda_radio_com1_power.is_none() {
-            self.thranda_radio_com1_power =
-                Some(DataRef::find("thranda/generic/com1/genCom1Pwr")?);
+impl PluginComponent for Radio {
+    fn info(&self) -> ComponentInfo {
+        ComponentInfo {
+            id: ID,
+            name: "Fix radio power",
+            description: "Power COM radios based on available bus voltage",
+            enabled_by_default: true,
         }
+    }
 
-        if self.thranda_radio_com2_power.is_none() {
-            self.thranda_radio_com2_power =
-                Some(DataRef::find("thranda/generic/com1/genCom2Pwr")?);
-        }
+    fn update_rate(&self) -> UpdateRate {
+        // Bus voltage changes slowly
+        UpdateRate::Hz(4.0)
+    }
 
-        Ok(())
+    fn bindings(&mut self, visit: &mut dyn FnMut(&mut dyn Bind)) {
+        visit(&mut self.bus_volts);
+        visit(&mut self.radio_gps1_power);
+        visit(&mut self.radio_gps2_power);
+        visit(&mut self.radio_com1_power);
+        visit(&mut self.radio_com2_power);
+        visit(&mut self.thranda_radio_com1_power);
+        visit(&mut self.thranda_radio_com2_power);
     }
-}
 
-impl PluginComponent for Radio {
-    fn is_initialized(&self) -> bool {
-        self.is_initialized
+    fn initialize(&mut self, aircraft: Aircraft) -> Result<(), PluginError> {
+        self.com_buses = aircraft.variant.com_buses();
+        Ok(())
     }
 
     fn update(&mut self) {
-        // We need to wait until all datarefs created by SASL are available...
-        if !self.is_initialized {
-            if self.initialize().is_ok() {
-                self.is_initialized = true;
-                debugln!("{PLUGIN_NAME} FixRadioPower component initialized");
-            } else {
-                return;
+        self.bus_volts.get(&mut self.bus_volts_slice);
+        let com_bus_volts = self.com_bus_volts();
+
+        let radio_com1_power = self.radio_com1_power.get();
+        let radio_com2_power = self.radio_com2_power.get();
+        let radio_gps1_power = self.radio_gps1_power.get();
+        let radio_gps2_power = self.radio_gps2_power.get();
+        let thranda_radio_com1_power = self.thranda_radio_com1_power.get();
+        let thranda_radio_com2_power = self.thranda_radio_com2_power.get();
+
+        let min_bus_volts = tunables().radio_min_bus_volts;
+
+        if com_bus_volts[0] > min_bus_volts && radio_gps1_power == 1 {
+            if self.radio_com1_power.set(thranda_radio_com1_power) {
+                log::override_write(
+                    Level::Info,
+                    ID,
+                    self.radio_com1_power.name(),
+                    radio_com1_power,
+                    thranda_radio_com1_power,
+                    format_args!(
+                        "bus 1 at {} V and GPS 1 on, follow COM1 switch",
+                        com_bus_volts[0]
+                    ),
+                );
             }
+        } else if radio_com1_power == 1 && self.radio_com1_power.set(0) {
+            log::override_write(
+                Level::Info,
+                ID,
+                self.radio_com1_power.name(),
+                radio_com1_power,
+                0,
+                format_args!(
+                    "bus 1 at {} V, needs more than {min_bus_volts} V, GPS 1 power {radio_gps1_power}",
+                    com_bus_volts[0]
+                ),
+            );
         }
 
-        if let Some(bus_volts) = self.bus_volts.as_ref() {
-            bus_volts.get(&mut self.bus_volts_slice);
-        }
-
-        let radio_com1_power =
-            self.radio_com1_power.as_ref().map_or(0, DataRead::get);
-        let radio_com2_power =
-            self.radio_com2_power.as_ref().map_or(0, DataRead::get);
-        let radio_gps1_power =
-            self.radio_gps1_power.as_ref().map_or(0, DataRead::get);
-        let radio_gps2_power =
-            self.radio_gps2_power.as_ref().map_or(0, DataRead::get);
-        let thranda_radio_com1_power = self
-            .thranda_radio_com1_power
-            .as_ref()
-            .map_or(0, DataRead::get);
-        let thranda_radio_com2_power = self
-            .thranda_radio_com2_power
-            .as_ref()
-            .map_or(0, DataRead::get);
-
-        if self.bus_volts_slice[0] > 21.0 && radio_gps1_power == 1 {
-            if radio_com1_power != thranda_radio_com1_power
-                && let Some(radio_com1_power) = self.radio_com1_power.as_mut()
-            {
-                radio_com1_power.set(thranda_radio_com1_power);
+        if com_bus_volts[1] > min_bus_volts && radio_gps2_power == 1 {
+            if self.radio_com2_power.set(thranda_radio_com2_power) {
+                log::override_write(
+                    Level::Info,
+                    ID,
+                    self.radio_com2_power.name(),
+                    radio_com2_power,
+                    thranda_radio_com2_power,
+                    format_args!(
+                        "bus 2 at {} V and GPS 2 on, follow COM2 switch",
+                        com_bus_volts[1]
+                    ),
+                );
             }
-        } else if radio_com1_power == 1
-            && let Some(radio_com1_power) = self.radio_com1_power.as_mut()
-        {
-            radio_com1_power.set(0);
+        } else if radio_com2_power == 1 && self.radio_com2_power.set(0) {
+            log::override_write(
+                Level::Info,
+                ID,
+                self.radio_com2_power.name(),
+                radio_com2_power,
+                0,
+                format_args!(
+                    "bus 2 at {} V, needs more than {min_bus_volts} V, GPS 2 power {radio_gps2_power}",
+                    com_bus_volts[1]
+                ),
+            );
         }
+    }
 
-        if self.bus_volts_slice[1] > 21.0 && radio_gps2_power == 1 {
-            if radio_com2_power != thranda_radio_com2_power
-                && let Some(radio_com2_power) = self.radio_com2_power.as_mut()
-            {
-                radio_com2_power.set(thranda_radio_com2_power);
-            }
-        } else if radio_com2_power == 1
-            && let Some(radio_com2_power) = self.radio_com2_power.as_mut()
-        {
-            radio_com2_power.set(0);
-        }
+    fn is_active(&self) -> bool {
+        let radio_gps1_power = self.radio_gps1_power.get();
+        let radio_gps2_power = self.radio_gps2_power.get();
+
+        let com_bus_volts = self.com_bus_volts();
+        let min_bus_volts = tunables().radio_min_bus_volts;
+        (com_bus_volts[0] > min_bus_volts && radio_gps1_power == 1)
+            || (com_bus_volts[1] > min_bus_volts && radio_gps2_power == 1)
     }
 }
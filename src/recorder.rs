@@ -0,0 +1,722 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+#[cfg(feature = "plugin")]
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+#[cfg(feature = "plugin")]
+use crate::aircraft::Aircraft;
+use crate::dataref::Sample;
+#[cfg(feature = "plugin")]
+use crate::dataref::{
+    ArrayRead, ArrayReadWrite, DataRefProvider, ScalarRead, ScalarReadWrite,
+    StringRead,
+};
+#[cfg(feature = "plugin")]
+use crate::log;
+#[cfg(feature = "plugin")]
+use crate::plugin::PLUGIN_NAME;
+use crate::plugin::PluginError;
+#[cfg(feature = "plugin")]
+use crate::settings::Settings;
+
+/// Folder in the plugin folder holding the recordings
+#[cfg(feature = "plugin")]
+pub static RECORDINGS_DIR: &str = "recordings";
+
+/// First bytes of a binary recording
+pub const MAGIC: &[u8; 8] = b"B146REC1";
+
+/// Column names of a CSV recording
+const CSV_COLUMNS: &str = "time,kind,type,dataref,value";
+
+/// File format of a recording
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One line per record, easy to read and to load into a spreadsheet
+    Csv,
+    /// Dataref names stored once, much smaller than CSV
+    Binary,
+}
+
+#[cfg(feature = "plugin")]
+impl Format {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Binary => "rec",
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Csv => "csv",
+            Self::Binary => "binary",
+        })
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv" => Ok(Self::Csv),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!("{value:?} is not one of csv, binary")),
+        }
+    }
+}
+
+/// Whether a value was seen or forced by the plugin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Value of a dataref a component works with, recorded when it changes
+    Read,
+    /// Value the plugin wrote
+    Write,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        })
+    }
+}
+
+/// A dataref value at `time` into the recording
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub time: Duration,
+    pub kind: Kind,
+    pub name: String,
+    pub value: Sample,
+}
+
+/// Comment lines at the start of a recording:
+/// plugin version, aircraft and settings
+#[cfg(feature = "plugin")]
+pub fn header(aircraft: Aircraft, settings: &Settings) -> String {
+    let version = aircraft
+        .version
+        .map_or_else(|| "unknown".to_string(), |version| version.to_string());
+    let mut header = format!(
+        "# {PLUGIN_NAME} recording\n# aircraft = {}\n\
+         # aircraft_version = {version}\n",
+        aircraft.variant.icao()
+    );
+    for line in settings.to_text().lines() {
+        if !line.starts_with('#') {
+            header.push_str("# ");
+            header.push_str(line);
+            header.push('\n');
+        }
+    }
+    header
+}
+
+/// Writes records in one of the [`Format`]s
+#[cfg(feature = "plugin")]
+enum Encoder {
+    Csv,
+    Binary {
+        /// Ids of the dataref names written so far
+        names: HashMap<String, u16>,
+    },
+}
+
+#[cfg(feature = "plugin")]
+impl Encoder {
+    fn new(format: Format) -> Self {
+        match format {
+            Format::Csv => Self::Csv,
+            Format::Binary => Self::Binary {
+                names: HashMap::new(),
+            },
+        }
+    }
+
+    fn header(&self, out: &mut dyn Write, header: &str) -> io::Result<()> {
+        match self {
+            Self::Csv => writeln!(out, "{header}{CSV_COLUMNS}"),
+            Self::Binary { .. } => {
+                out.write_all(MAGIC)?;
+                write_len(out, header.len())?;
+                out.write_all(header.as_bytes())
+            }
+        }
+    }
+
+    fn record(
+        &mut self,
+        out: &mut dyn Write,
+        record: &Record,
+    ) -> io::Result<()> {
+        match self {
+            Self::Csv => {
+                let (kind, value) = match &record.value {
+                    Sample::Int(value) => ("int", value.to_string()),
+                    Sample::Float(value) => ("float", value.to_string()),
+                    Sample::FloatArray(values) => (
+                        "floats",
+                        values
+                            .iter()
+                            .map(f32::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    ),
+                };
+                writeln!(
+                    out,
+                    "{:.3},{},{kind},{},{value}",
+                    record.time.as_secs_f64(),
+                    record.kind,
+                    record.name
+                )
+            }
+            Self::Binary { names } => {
+                let id = if let Some(id) = names.get(&record.name) {
+                    *id
+                } else {
+                    // First record of this dataref, store its name once
+                    let id = u16::try_from(names.len())
+                        .map_err(|_| io::Error::other("too many datarefs"))?;
+                    out.write_all(&[0])?;
+                    out.write_all(&id.to_le_bytes())?;
+                    write_len(out, record.name.len())?;
+                    out.write_all(record.name.as_bytes())?;
+                    names.insert(record.name.clone(), id);
+                    id
+                };
+
+                let tag = match record.kind {
+                    Kind::Read => 1_u8,
+                    Kind::Write => 2,
+                };
+                let millis =
+                    u32::try_from(record.time.as_millis()).unwrap_or(u32::MAX);
+                out.write_all(&[tag])?;
+                out.write_all(&millis.to_le_bytes())?;
+                out.write_all(&id.to_le_bytes())?;
+                match &record.value {
+                    Sample::Int(value) => {
+                        out.write_all(&[0])?;
+                        out.write_all(&value.to_le_bytes())
+                    }
+                    Sample::Float(value) => {
+                        out.write_all(&[1])?;
+                        out.write_all(&value.to_le_bytes())
+                    }
+                    Sample::FloatArray(values) => {
+                        out.write_all(&[2])?;
+                        write_len(out, values.len())?;
+                        for value in values {
+                            out.write_all(&value.to_le_bytes())?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Length prefix of the binary format
+#[cfg(feature = "plugin")]
+fn write_len(out: &mut dyn Write, len: usize) -> io::Result<()> {
+    let len = u16::try_from(len)
+        .map_err(|_| io::Error::other("value too long to record"))?;
+    out.write_all(&len.to_le_bytes())
+}
+
+/// Header and records of a recording in either [`Format`]
+pub fn parse(bytes: &[u8]) -> Result<(String, Vec<Record>), PluginError> {
+    match bytes.strip_prefix(MAGIC) {
+        Some(rest) => parse_binary(rest),
+        None => parse_csv(bytes),
+    }
+}
+
+fn invalid(reason: impl Display) -> PluginError {
+    PluginError::InvalidRecording(reason.to_string())
+}
+
+fn parse_csv(bytes: &[u8]) -> Result<(String, Vec<Record>), PluginError> {
+    let text = std::str::from_utf8(bytes).map_err(invalid)?;
+    let mut header = String::new();
+    let mut records = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        if line.starts_with('#') {
+            header.push_str(line);
+            header.push('\n');
+            continue;
+        }
+        if line.is_empty() || line == CSV_COLUMNS {
+            continue;
+        }
+
+        let invalid_line =
+            |reason: &str| invalid(format!("line {}: {reason}", index + 1));
+        let fields: Vec<_> = line.splitn(5, ',').collect();
+        let [time, kind, kind_of_value, name, value] = fields[..] else {
+            return Err(invalid_line("expected 5 columns"));
+        };
+        let time = time
+            .parse()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| invalid_line("invalid time"))?;
+        let kind = match kind {
+            "read" => Kind::Read,
+            "write" => Kind::Write,
+            _ => return Err(invalid_line("invalid kind")),
+        };
+        let value = match kind_of_value {
+            "int" => value.parse().map(Sample::Int).ok(),
+            "float" => value.parse().map(Sample::Float).ok(),
+            "floats" => value
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map(Sample::FloatArray)
+                .ok(),
+            _ => None,
+        }
+        .ok_or_else(|| invalid_line("invalid value"))?;
+
+        records.push(Record {
+            time,
+            kind,
+            name: name.to_string(),
+            value,
+        });
+    }
+
+    Ok((header, records))
+}
+
+/// Reads the binary format back, see [`Encoder::record`]
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl BinaryReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], PluginError> {
+        let (taken, rest) = self
+            .bytes
+            .split_first_chunk()
+            .ok_or_else(|| invalid("unexpected end of file"))?;
+        self.bytes = rest;
+        Ok(*taken)
+    }
+
+    fn len(&mut self) -> Result<usize, PluginError> {
+        Ok(usize::from(u16::from_le_bytes(self.take()?)))
+    }
+
+    fn text(&mut self) -> Result<String, PluginError> {
+        let len = self.len()?;
+        if self.bytes.len() < len {
+            return Err(invalid("unexpected end of file"));
+        }
+        let (text, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        String::from_utf8(text.to_vec()).map_err(invalid)
+    }
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<(String, Vec<Record>), PluginError> {
+    let mut reader = BinaryReader { bytes };
+    let header = reader.text()?;
+    let mut names = Vec::new();
+    let mut records = Vec::new();
+
+    while !reader.bytes.is_empty() {
+        let kind = match reader.take::<1>()? {
+            [0] => {
+                // Ids are handed out in order
+                let _id = reader.take::<2>()?;
+                names.push(reader.text()?);
+                continue;
+            }
+            [1] => Kind::Read,
+            [2] => Kind::Write,
+            [tag] => return Err(invalid(format!("unknown record {tag}"))),
+        };
+        let millis = u32::from_le_bytes(reader.take()?);
+        let id = usize::from(u16::from_le_bytes(reader.take()?));
+        let name = names
+            .get(id)
+            .ok_or_else(|| invalid(format!("unknown dataref id {id}")))?;
+        let value = match reader.take::<1>()? {
+            [0] => Sample::Int(i32::from_le_bytes(reader.take()?)),
+            [1] => Sample::Float(f32::from_le_bytes(reader.take()?)),
+            [2] => {
+                let count = reader.len()?;
+                let values = (0..count)
+                    .map(|_| reader.take().map(f32::from_le_bytes))
+                    .collect::<Result<_, _>>()?;
+                Sample::FloatArray(values)
+            }
+            [kind] => {
+                return Err(invalid(format!("unknown value type {kind}")));
+            }
+        };
+
+        records.push(Record {
+            time: Duration::from_millis(u64::from(millis)),
+            kind,
+            name: name.clone(),
+            value,
+        });
+    }
+
+    Ok((header, records))
+}
+
+/// A recording in progress
+#[cfg(feature = "plugin")]
+struct Recording {
+    out: Box<dyn Write>,
+    encoder: Encoder,
+    time: Duration,
+
+    /// Last recorded value per dataref read
+    last: HashMap<&'static str, Sample>,
+}
+
+/// Captures dataref values and writes while switched on
+#[cfg(feature = "plugin")]
+pub struct Recorder {
+    recording: Option<Recording>,
+}
+
+#[cfg(feature = "plugin")]
+impl Recorder {
+    pub const fn new() -> Self {
+        Self { recording: None }
+    }
+
+    pub const fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Start recording to `out`, replacing any recording in progress
+    pub fn start(
+        &mut self,
+        mut out: Box<dyn Write>,
+        format: Format,
+        header: &str,
+    ) -> Result<(), PluginError> {
+        self.stop();
+        let encoder = Encoder::new(format);
+        encoder.header(&mut out, header)?;
+        self.recording = Some(Recording {
+            out,
+            encoder,
+            time: Duration::ZERO,
+            last: HashMap::new(),
+        });
+        Ok(())
+    }
+
+    /// Start recording to a new timestamped file in `dir`.
+    /// Returns the path of the file.
+    pub fn start_file(
+        &mut self,
+        dir: &Path,
+        format: Format,
+        header: &str,
+    ) -> Result<PathBuf, PluginError> {
+        fs::create_dir_all(dir)?;
+        let stamp = log::timestamp(SystemTime::now());
+        let name = format!(
+            "tweaks-{}.{}",
+            stamp[..19].replace([' ', ':'], "-"),
+            format.extension()
+        );
+        let path = dir.join(name);
+        let file = BufWriter::new(File::create(&path)?);
+        self.start(Box::new(file), format, header)?;
+        Ok(path)
+    }
+
+    /// Finish the recording in progress, if any
+    pub fn stop(&mut self) {
+        if let Some(mut recording) = self.recording.take()
+            && let Err(error) = recording.out.flush()
+        {
+            warn!("unable to finish recording: {error}");
+        }
+    }
+
+    /// Move the recording time on by a flight loop
+    pub fn advance(&mut self, elapsed: Duration) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.time += elapsed;
+        }
+    }
+
+    /// Record the value of a dataref read if it changed
+    pub fn read(&mut self, name: &'static str, value: Sample) {
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
+        if recording.last.get(name) == Some(&value) {
+            return;
+        }
+        recording.last.insert(name, value.clone());
+        self.record(Kind::Read, name, value);
+    }
+
+    /// Record a value the plugin wrote
+    pub fn write(&mut self, name: &str, value: Sample) {
+        self.record(Kind::Write, name, value);
+    }
+
+    fn record(&mut self, kind: Kind, name: &str, value: Sample) {
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
+        let record = Record {
+            time: recording.time,
+            kind,
+            name: name.to_string(),
+            value,
+        };
+        if let Err(error) =
+            recording.encoder.record(&mut recording.out, &record)
+        {
+            warn!("recording stopped: {error}");
+            self.recording = None;
+        }
+    }
+}
+
+/// Writeable dataref whose writes go into the recording
+#[cfg(feature = "plugin")]
+struct RecordedWrites<D: ?Sized> {
+    name: String,
+    dataref: Box<D>,
+    recorder: Rc<RefCell<Recorder>>,
+}
+
+#[cfg(feature = "plugin")]
+impl<D: ?Sized> RecordedWrites<D> {
+    fn record(&self, value: impl FnOnce() -> Sample) {
+        if let Ok(mut recorder) = self.recorder.try_borrow_mut()
+            && recorder.is_recording()
+        {
+            recorder.write(&self.name, value());
+        }
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<T> ScalarRead<T> for RecordedWrites<dyn ScalarReadWrite<T>> {
+    fn get(&self) -> T {
+        self.dataref.get()
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<T: Copy + Into<Sample>> ScalarReadWrite<T>
+    for RecordedWrites<dyn ScalarReadWrite<T>>
+{
+    fn set(&mut self, value: T) {
+        self.dataref.set(value);
+        self.record(|| value.into());
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl ArrayRead<f32> for RecordedWrites<dyn ArrayReadWrite<f32>> {
+    fn get(&self, dest: &mut [f32]) -> usize {
+        self.dataref.get(dest)
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl ArrayReadWrite<f32> for RecordedWrites<dyn ArrayReadWrite<f32>> {
+    fn set(&mut self, values: &[f32]) {
+        self.dataref.set(values);
+        self.record(|| values.into());
+    }
+}
+
+/// Looks up datarefs in `data`,
+/// passing writes to found datarefs on to the [`Recorder`]
+#[cfg(feature = "plugin")]
+pub struct RecordingDataRefs<D> {
+    data: D,
+    recorder: Rc<RefCell<Recorder>>,
+}
+
+#[cfg(feature = "plugin")]
+impl<D> RecordingDataRefs<D> {
+    pub const fn new(data: D, recorder: Rc<RefCell<Recorder>>) -> Self {
+        Self { data, recorder }
+    }
+
+    fn recorded<T: ?Sized>(
+        &self,
+        name: &str,
+        dataref: Box<T>,
+    ) -> RecordedWrites<T> {
+        RecordedWrites {
+            name: name.to_string(),
+            dataref,
+            recorder: Rc::clone(&self.recorder),
+        }
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<D: DataRefProvider> DataRefProvider for RecordingDataRefs<D> {
+    fn find_i32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<i32>>, PluginError> {
+        self.data.find_i32(name)
+    }
+
+    fn find_i32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        let dataref = self.data.find_i32_writeable(name)?;
+        Ok(Box::new(self.recorded(name, dataref)))
+    }
+
+    fn find_f32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<f32>>, PluginError> {
+        self.data.find_f32(name)
+    }
+
+    fn find_f32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        let dataref = self.data.find_f32_writeable(name)?;
+        Ok(Box::new(self.recorded(name, dataref)))
+    }
+
+    fn find_f32_array(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayRead<f32>>, PluginError> {
+        self.data.find_f32_array(name)
+    }
+
+    fn find_f32_array_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayReadWrite<f32>>, PluginError> {
+        let dataref = self.data.find_f32_array_writeable(name)?;
+        Ok(Box::new(self.recorded(name, dataref)))
+    }
+
+    fn find_string(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn StringRead>, PluginError> {
+        self.data.find_string(name)
+    }
+
+    fn create_i32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        self.data.create_i32(name, writeable)
+    }
+
+    fn create_f32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        self.data.create_f32(name, writeable)
+    }
+}
+
+#[cfg(all(test, feature = "plugin"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Encoder, Format, Kind, Record, parse};
+    use crate::dataref::Sample;
+
+    #[test]
+    fn recordings_read_back_in_both_formats() {
+        let records = vec![
+            Record {
+                time: Duration::ZERO,
+                kind: Kind::Read,
+                name: "sim/cockpit2/electrical/bus_volts".to_string(),
+                value: Sample::FloatArray(vec![27.5, 0.25]),
+            },
+            Record {
+                time: Duration::from_millis(1250),
+                kind: Kind::Write,
+                name: "sim/operation/override/override_GPU_volts".to_string(),
+                value: Sample::Int(1),
+            },
+            Record {
+                time: Duration::from_millis(1300),
+                kind: Kind::Read,
+                name: "sim/cockpit2/electrical/bus_volts".to_string(),
+                value: Sample::FloatArray(Vec::new()),
+            },
+        ];
+        let header = "# aircraft = B462\n";
+
+        for format in [Format::Csv, Format::Binary] {
+            let mut encoder = Encoder::new(format);
+            let mut bytes = Vec::new();
+            encoder.header(&mut bytes, header).unwrap();
+            for record in &records {
+                encoder.record(&mut bytes, record).unwrap();
+            }
+
+            assert_eq!(
+                parse(&bytes).unwrap(),
+                (header.to_string(), records.clone()),
+                "{format}"
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_binary_recordings_are_rejected() {
+        let mut encoder = Encoder::new(Format::Binary);
+        let mut bytes = Vec::new();
+        encoder.header(&mut bytes, "").unwrap();
+        let record = Record {
+            time: Duration::ZERO,
+            kind: Kind::Write,
+            name: "sim/operation/override/override_GPU_volts".to_string(),
+            value: Sample::Int(1),
+        };
+        encoder.record(&mut bytes, &record).unwrap();
+
+        assert!(parse(&bytes).is_ok());
+        assert!(parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(parse(b"0.000,read,int").is_err());
+    }
+}
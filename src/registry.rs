@@ -0,0 +1,325 @@
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::aircraft::{self, Aircraft, AircraftVersion, Compatibility};
+use crate::component::{ComponentInfo, PluginComponent, UpdateRate};
+use crate::dataref::{DataRefProvider, Sample};
+use crate::event::Event;
+use crate::profile::UpdateStats;
+
+// Components
+use crate::gpu::GeneratorVolts;
+use crate::hsi::CopilotHSI;
+use crate::nosewheel_steering::NosewheelSteering;
+use crate::radio::Radio;
+use crate::throttle_levers::ThrottleLevers;
+
+/// Initialization progress of a component
+#[derive(Clone, Debug, PartialEq)]
+pub enum InitState {
+    /// Waiting for datarefs, `reason` tells which one is missing
+    Pending {
+        waited: Duration,
+        reason: Option<String>,
+    },
+    Ready,
+    /// Gave up waiting for datarefs
+    Failed {
+        reason: String,
+    },
+}
+
+impl InitState {
+    pub const fn new() -> Self {
+        Self::Pending {
+            waited: Duration::ZERO,
+            reason: None,
+        }
+    }
+}
+
+pub struct RegisteredComponent {
+    pub info: ComponentInfo,
+    pub enabled: bool,
+    pub init: InitState,
+    pub rate: UpdateRate,
+
+    /// Time since the last update, for [`UpdateRate::Hz`]
+    pub since_update: Duration,
+
+    /// Time left until a suspended component updates again
+    pub suspended: Duration,
+
+    /// When the component last wrote a dataref or was active
+    pub last_active: Option<Instant>,
+
+    /// Updates timed while `profile_updates` is set
+    pub profile: UpdateStats,
+
+    pub component: Box<dyn PluginComponent>,
+}
+
+/// All components known to the plugin, in update order
+pub struct ComponentRegistry {
+    /// Where the components look up their datarefs
+    data: Rc<dyn DataRefProvider>,
+
+    /// Handed to the components when they initialize
+    aircraft: Aircraft,
+
+    components: Vec<RegisteredComponent>,
+
+    /// Events waiting for [`ComponentRegistry::dispatch_events`]
+    events: Vec<Event>,
+}
+
+impl ComponentRegistry {
+    pub fn new(data: Rc<dyn DataRefProvider>, aircraft: Aircraft) -> Self {
+        Self {
+            data,
+            aircraft,
+            components: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Create registry with every fix this plugin provides
+    pub fn with_components(
+        data: Rc<dyn DataRefProvider>,
+        aircraft: Aircraft,
+    ) -> Self {
+        let mut registry = Self::new(data, aircraft);
+        registry.register(GeneratorVolts::new());
+        registry.register(CopilotHSI::new());
+        registry.register(NosewheelSteering::new());
+        registry.register(Radio::new());
+        registry.register(ThrottleLevers::new());
+        registry
+    }
+
+    pub fn register(&mut self, component: impl PluginComponent + 'static) {
+        let info = component.info();
+        debug_assert!(
+            self.get(info.id).is_none(),
+            "component {} registered twice",
+            info.id
+        );
+        debug!("{}: {}", info.name, info.description);
+
+        let rate = match component.update_rate() {
+            // The time between updates has to be a valid duration
+            UpdateRate::Hz(hz)
+                if Duration::try_from_secs_f32(hz.recip()).is_err() =>
+            {
+                warn!("{} asked for {hz} Hz, updating every frame", info.name);
+                UpdateRate::EveryFrame
+            }
+            rate => rate,
+        };
+        self.components.push(RegisteredComponent {
+            info,
+            enabled: info.enabled_by_default,
+            init: InitState::new(),
+            rate,
+            since_update: Duration::ZERO,
+            last_active: None,
+            suspended: Duration::ZERO,
+            profile: UpdateStats::default(),
+            component: Box::new(component),
+        });
+    }
+
+    pub fn data(&self) -> Rc<dyn DataRefProvider> {
+        Rc::clone(&self.data)
+    }
+
+    /// The loaded aircraft, for rules which depend on the variant
+    pub const fn aircraft(&self) -> Aircraft {
+        self.aircraft
+    }
+
+    /// Components initialized from now on see `aircraft`
+    #[cfg(all(test, feature = "plugin"))]
+    pub const fn set_aircraft(&mut self, aircraft: Aircraft) {
+        self.aircraft = aircraft;
+    }
+
+    pub fn get(&self, id: &str) -> Option<&RegisteredComponent> {
+        self.components.iter().find(|comp| comp.info.id == id)
+    }
+
+    /// Current value of the dataref `name`
+    /// if a component has it bound
+    pub fn sample(&mut self, name: &str) -> Option<Sample> {
+        let mut sample = None;
+        for registered in &mut self.components {
+            registered.component.bindings(&mut |binding| {
+                if sample.is_none() && binding.name() == name {
+                    sample = binding.sample();
+                }
+            });
+        }
+        sample
+    }
+
+    /// Enable or disable a component at runtime.
+    /// Disabling a component restores what it took over from the simulator.
+    /// Returns `false` if there is no component with this id.
+    #[cfg(feature = "plugin")]
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) -> bool {
+        match self.components.iter_mut().find(|comp| comp.info.id == id) {
+            Some(comp) => {
+                if comp.enabled && !enabled {
+                    comp.component.teardown();
+                    comp.init = InitState::new();
+                }
+                if comp.enabled != enabled {
+                    self.events.push(Event::ComponentEnabled {
+                        id: comp.info.id,
+                        enabled,
+                    });
+                }
+                comp.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Switch off by default the components whose bug is fixed
+    /// in the installed aircraft `version`.
+    /// An enable flag in the settings still takes precedence.
+    pub fn apply_compatibility(
+        &mut self,
+        table: &[Compatibility],
+        version: AircraftVersion,
+    ) {
+        for registered in &mut self.components {
+            if let Some(fixed_in) =
+                aircraft::fixed_in(table, registered.info.id, version)
+            {
+                registered.info.enabled_by_default = false;
+                registered.enabled = false;
+                info!(
+                    "{} not needed, fixed in BAe 146 v{fixed_in}",
+                    registered.info.name
+                );
+            }
+        }
+    }
+
+    /// Pause the updates of a component for `duration`,
+    /// e.g. while another plugin drives what it writes.
+    /// A zero `duration` resumes it.
+    /// Returns `false` if there is no component with this id.
+    #[cfg(feature = "plugin")]
+    pub fn suspend(&mut self, id: &str, duration: Duration) -> bool {
+        let Some(comp) =
+            self.components.iter_mut().find(|comp| comp.info.id == id)
+        else {
+            return false;
+        };
+
+        let suspended = !duration.is_zero();
+        if comp.suspended.is_zero() == suspended {
+            self.events.push(Event::ComponentSuspended {
+                id: comp.info.id,
+                suspended,
+            });
+        }
+        if suspended {
+            info!(
+                "{} suspended for {:.1}s",
+                comp.info.name,
+                duration.as_secs_f32()
+            );
+        } else {
+            info!("{} resumed", comp.info.name);
+        }
+        comp.suspended = duration;
+        true
+    }
+
+    /// Resume suspended components whose time is up
+    pub fn count_down_suspensions(&mut self, elapsed: Duration) {
+        for comp in &mut self.components {
+            if comp.suspended.is_zero() {
+                continue;
+            }
+            comp.suspended = comp.suspended.saturating_sub(elapsed);
+            if comp.suspended.is_zero() {
+                info!("{} resumed", comp.info.name);
+                #[cfg(feature = "plugin")]
+                self.events.push(Event::ComponentSuspended {
+                    id: comp.info.id,
+                    suspended: false,
+                });
+            }
+        }
+    }
+
+    pub fn publish(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /// Hand all published events to the initialized components
+    /// which aren't suspended and want them.
+    /// Returns the events handed out.
+    pub fn dispatch_events(&mut self) -> Vec<Event> {
+        let events = std::mem::take(&mut self.events);
+        for event in &events {
+            debug!("event: {event}");
+            for registered in self.components.iter_mut().filter(|comp| {
+                comp.enabled
+                    && comp.init == InitState::Ready
+                    && comp.suspended.is_zero()
+                    && comp.component.events().contains(&event.kind())
+            }) {
+                registered.component.on_event(event);
+            }
+        }
+        events
+    }
+
+    /// Run a one-shot action of an initialized component.
+    /// Returns `false` if the component can't run it right now.
+    #[cfg(feature = "plugin")]
+    pub fn run_action(&mut self, id: &str, action: &str) -> bool {
+        let Some(registered) =
+            self.components.iter_mut().find(|comp| comp.info.id == id)
+        else {
+            return false;
+        };
+
+        if registered.init != InitState::Ready {
+            warn!(
+                "{} is not initialized, skipping {action}",
+                registered.info.name
+            );
+            return false;
+        }
+
+        registered.component.run_action(action);
+        info!("{} ran {action}", registered.info.name);
+        true
+    }
+
+    /// Restore what the enabled components took over from the simulator
+    #[cfg(feature = "plugin")]
+    pub fn teardown(&mut self) {
+        for registered in self.iter_mut().filter(|comp| comp.enabled) {
+            registered.component.teardown();
+            registered.init = InitState::new();
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredComponent> {
+        self.components.iter()
+    }
+
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl Iterator<Item = &mut RegisteredComponent> {
+        self.components.iter_mut()
+    }
+}
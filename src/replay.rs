@@ -0,0 +1,460 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::aircraft::{Aircraft, AircraftVersion, COMPATIBILITY, Variant};
+#[cfg(feature = "plugin")]
+use crate::dataref::StringRead;
+use crate::dataref::{
+    ArrayRead, ArrayReadWrite, DataRefProvider, Sample, ScalarRead,
+    ScalarReadWrite,
+};
+use crate::handler::FlightLoopHandler;
+use crate::plugin::PluginError;
+use crate::recorder::{self, Kind, Record};
+use crate::registry::ComponentRegistry;
+use crate::settings::{Settings, SettingsScope};
+
+/// Time between two replayed flight loops
+pub const FRAME_TIME: Duration = Duration::from_millis(50);
+
+/// Flight loops run after the last record, for updates still due
+const TRAILING_FRAMES: u32 = 20;
+
+/// Aircraft, settings and records of a recording
+struct Session {
+    aircraft: Aircraft,
+    settings: Settings,
+    records: Vec<Record>,
+}
+
+impl Session {
+    fn parse(bytes: &[u8]) -> Result<Self, PluginError> {
+        let (header, records) = recorder::parse(bytes)?;
+
+        let mut variant = None;
+        let mut version = None;
+        let mut settings = String::new();
+        for line in header.lines() {
+            let Some((key, value)) = line
+                .trim_start_matches('#')
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+            else {
+                continue;
+            };
+            match key {
+                "aircraft" => {
+                    variant =
+                        Some(Variant::from_icao(value).ok_or_else(|| {
+                            PluginError::AircraftNotSupported(value.into())
+                        })?);
+                }
+                "aircraft_version" => {
+                    version = value.parse::<AircraftVersion>().ok();
+                }
+                _ => {
+                    settings.push_str(line.trim_start_matches('#'));
+                    settings.push('\n');
+                }
+            }
+        }
+
+        let variant = variant.ok_or_else(|| {
+            PluginError::InvalidRecording("no aircraft in header".into())
+        })?;
+        Ok(Self {
+            aircraft: Aircraft { variant, version },
+            settings: Settings::parse(&settings)?,
+            records,
+        })
+    }
+}
+
+/// A write of the replayed components
+struct Replayed {
+    record: Record,
+
+    /// Whether the write changed the dataref value
+    changed: bool,
+}
+
+/// Dataref values during the replay
+struct Table {
+    time: Duration,
+    values: HashMap<String, Sample>,
+    writes: Vec<Replayed>,
+}
+
+impl Table {
+    /// Every dataref in `records`, with its first value read.
+    /// Datarefs written before being read start out as zero.
+    fn new(records: &[Record]) -> Self {
+        let mut values = HashMap::new();
+        for record in records {
+            values.entry(record.name.clone()).or_insert_with(|| {
+                match (&record.kind, &record.value) {
+                    (Kind::Read, value) => value.clone(),
+                    (Kind::Write, Sample::Int(_)) => Sample::Int(0),
+                    (Kind::Write, Sample::Float(_)) => Sample::Float(0.0),
+                    (Kind::Write, Sample::FloatArray(values)) => {
+                        Sample::FloatArray(vec![0.0; values.len()])
+                    }
+                }
+            });
+        }
+
+        Self {
+            time: Duration::ZERO,
+            values,
+            writes: Vec::new(),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: Sample) {
+        let previous = self.values.insert(name.to_string(), value.clone());
+        self.writes.push(Replayed {
+            record: Record {
+                time: self.time,
+                kind: Kind::Write,
+                name: name.to_string(),
+                value: value.clone(),
+            },
+            changed: previous != Some(value),
+        });
+    }
+}
+
+/// Dataref of the replay [`Table`]
+struct ReplayDataRef {
+    table: Rc<RefCell<Table>>,
+    name: String,
+}
+
+impl ReplayDataRef {
+    fn value(&self) -> Sample {
+        self.table.borrow().values[&self.name].clone()
+    }
+}
+
+impl ScalarRead<i32> for ReplayDataRef {
+    fn get(&self) -> i32 {
+        match self.value() {
+            Sample::Int(value) => value,
+            _ => 0,
+        }
+    }
+}
+
+impl ScalarReadWrite<i32> for ReplayDataRef {
+    fn set(&mut self, value: i32) {
+        self.table.borrow_mut().set(&self.name, Sample::Int(value));
+    }
+}
+
+impl ScalarRead<f32> for ReplayDataRef {
+    fn get(&self) -> f32 {
+        match self.value() {
+            Sample::Float(value) => value,
+            _ => 0.0,
+        }
+    }
+}
+
+impl ScalarReadWrite<f32> for ReplayDataRef {
+    fn set(&mut self, value: f32) {
+        self.table
+            .borrow_mut()
+            .set(&self.name, Sample::Float(value));
+    }
+}
+
+impl ArrayRead<f32> for ReplayDataRef {
+    fn get(&self, dest: &mut [f32]) -> usize {
+        let Sample::FloatArray(values) = self.value() else {
+            return 0;
+        };
+        let count = values.len().min(dest.len());
+        dest[..count].copy_from_slice(&values[..count]);
+        count
+    }
+}
+
+impl ArrayReadWrite<f32> for ReplayDataRef {
+    fn set(&mut self, values: &[f32]) {
+        // Like X-Plane, only the given values change
+        let Sample::FloatArray(mut array) = self.value() else {
+            return;
+        };
+        let count = values.len().min(array.len());
+        array[..count].copy_from_slice(&values[..count]);
+        self.table
+            .borrow_mut()
+            .set(&self.name, Sample::FloatArray(array));
+    }
+}
+
+/// Datarefs of a recording, writeable as the plugin's writes are unknown
+struct ReplayDataRefs(Rc<RefCell<Table>>);
+
+impl ReplayDataRefs {
+    /// Dataref `name` if recorded with values `matches` accepts
+    fn find(
+        &self,
+        name: &str,
+        matches: fn(&Sample) -> bool,
+    ) -> Result<ReplayDataRef, PluginError> {
+        if !self.0.borrow().values.get(name).is_some_and(matches) {
+            return Err(PluginError::DataRefNotFound(name.to_string()));
+        }
+        Ok(ReplayDataRef {
+            table: Rc::clone(&self.0),
+            name: name.to_string(),
+        })
+    }
+
+    fn find_int(&self, name: &str) -> Result<ReplayDataRef, PluginError> {
+        self.find(name, |value| matches!(value, Sample::Int(_)))
+    }
+
+    fn find_float(&self, name: &str) -> Result<ReplayDataRef, PluginError> {
+        self.find(name, |value| matches!(value, Sample::Float(_)))
+    }
+
+    fn find_floats(&self, name: &str) -> Result<ReplayDataRef, PluginError> {
+        self.find(name, |value| matches!(value, Sample::FloatArray(_)))
+    }
+}
+
+impl DataRefProvider for ReplayDataRefs {
+    fn find_i32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<i32>>, PluginError> {
+        Ok(Box::new(self.find_int(name)?))
+    }
+
+    fn find_i32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        Ok(Box::new(self.find_int(name)?))
+    }
+
+    fn find_f32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<f32>>, PluginError> {
+        Ok(Box::new(self.find_float(name)?))
+    }
+
+    fn find_f32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        Ok(Box::new(self.find_float(name)?))
+    }
+
+    fn find_f32_array(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayRead<f32>>, PluginError> {
+        Ok(Box::new(self.find_floats(name)?))
+    }
+
+    fn find_f32_array_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayReadWrite<f32>>, PluginError> {
+        Ok(Box::new(self.find_floats(name)?))
+    }
+
+    #[cfg(feature = "plugin")]
+    fn find_string(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn StringRead>, PluginError> {
+        Err(PluginError::DataRefNotFound(name.to_string()))
+    }
+
+    #[cfg(feature = "plugin")]
+    fn create_i32(
+        &self,
+        name: &str,
+        _writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        Err(PluginError::DataRefNotCreated(name.to_string()))
+    }
+
+    #[cfg(feature = "plugin")]
+    fn create_f32(
+        &self,
+        name: &str,
+        _writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        Err(PluginError::DataRefNotCreated(name.to_string()))
+    }
+}
+
+/// How a write compares with the recording
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Made now and in the recording
+    Same,
+    /// Only made by the current code
+    Added,
+    /// Only in the recording
+    Missing,
+}
+
+/// Writes of the replay next to the writes in the recording
+pub struct Report {
+    pub writes: Vec<(Outcome, Record)>,
+}
+
+impl Report {
+    /// Match the writes per dataref in order.
+    /// Writes of the current code which leave the value unchanged,
+    /// e.g. overrides taken again at the start of the replay,
+    /// are only listed if the recording has them too.
+    fn compare(recorded: &[Record], replayed: Vec<Replayed>) -> Self {
+        let mut writes = Vec::new();
+        let mut pending: HashMap<&str, Vec<&Record>> = HashMap::new();
+        for record in
+            recorded.iter().filter(|record| record.kind == Kind::Write)
+        {
+            pending.entry(&record.name).or_default().push(record);
+        }
+        let mut next: HashMap<&str, usize> = HashMap::new();
+
+        for Replayed { record, changed } in replayed {
+            let recorded = pending
+                .get(record.name.as_str())
+                .map_or(&[][..], Vec::as_slice);
+            let start = next.get(record.name.as_str()).copied().unwrap_or(0);
+            match recorded[start..]
+                .iter()
+                .position(|other| other.value == record.value)
+            {
+                Some(offset) => {
+                    for skipped in &recorded[start..start + offset] {
+                        writes.push((Outcome::Missing, (*skipped).clone()));
+                    }
+                    let name = recorded[start].name.as_str();
+                    next.insert(name, start + offset + 1);
+                    writes.push((Outcome::Same, record));
+                }
+                None if changed => writes.push((Outcome::Added, record)),
+                None => {}
+            }
+        }
+
+        for (name, recorded) in pending {
+            let start = next.get(name).copied().unwrap_or(0);
+            for missing in &recorded[start..] {
+                writes.push((Outcome::Missing, (*missing).clone()));
+            }
+        }
+
+        writes.sort_by_key(|(_, record)| record.time);
+        Self { writes }
+    }
+
+    /// Number of writes made only now or only in the recording
+    #[must_use]
+    pub fn differences(&self) -> usize {
+        self.writes
+            .iter()
+            .filter(|(outcome, _)| *outcome != Outcome::Same)
+            .count()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (outcome, record) in &self.writes {
+            let marker = match outcome {
+                Outcome::Same => '=',
+                Outcome::Added => '+',
+                Outcome::Missing => '-',
+            };
+            writeln!(
+                f,
+                "{marker} {:9.3} {} = {}",
+                record.time.as_secs_f64(),
+                record.name,
+                record.value
+            )?;
+        }
+        writeln!(
+            f,
+            "{} writes, {} different from the recording",
+            self.writes.len(),
+            self.differences()
+        )
+    }
+}
+
+/// Replay a recording in either format through all components
+/// and compare their writes with the recorded ones.
+/// The recorded settings apply during the replay,
+/// the previous settings are restored afterwards, also on panics.
+///
+/// # Errors
+/// Fails if the recording can't be read or is for an unknown aircraft.
+pub fn run(bytes: &[u8]) -> Result<Report, PluginError> {
+    let Session {
+        aircraft,
+        settings,
+        records,
+    } = Session::parse(bytes)?;
+
+    let table = Rc::new(RefCell::new(Table::new(&records)));
+    let mut registry = ComponentRegistry::with_components(
+        Rc::new(ReplayDataRefs(Rc::clone(&table))),
+        aircraft,
+    );
+    if let Some(version) = aircraft.version {
+        registry.apply_compatibility(COMPATIBILITY, version);
+    }
+    for registered in registry.iter_mut() {
+        registered.enabled = settings.component_enabled(&registered.info);
+    }
+    let _settings = SettingsScope::enter(settings);
+    let mut handler = FlightLoopHandler::new(Rc::new(RefCell::new(registry)));
+
+    let end = records.last().map_or(Duration::ZERO, |record| record.time)
+        + FRAME_TIME * TRAILING_FRAMES;
+    let mut reads = records
+        .iter()
+        .filter(|record| record.kind == Kind::Read)
+        .peekable();
+    let mut apply_reads = |time: Duration| {
+        let mut table = table.borrow_mut();
+        table.time = time;
+        while let Some(read) = reads.next_if(|read| read.time <= time) {
+            table.values.insert(read.name.clone(), read.value.clone());
+        }
+    };
+
+    // Recordings usually start with the fixes running, so they
+    // initialize against the first values before the clock starts.
+    // Components still pending fail after the init timeout.
+    apply_reads(Duration::ZERO);
+    let mut elapsed = Duration::ZERO;
+    while !handler.update(elapsed) {
+        elapsed = FRAME_TIME;
+    }
+
+    let mut time = FRAME_TIME;
+    while time <= end {
+        apply_reads(time);
+        handler.update(FRAME_TIME);
+        time += FRAME_TIME;
+    }
+
+    let replayed = std::mem::take(&mut table.borrow_mut().writes);
+    Ok(Report::compare(&records, replayed))
+}
@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use crate::settings::{SETTINGS, SettingsScope};
+use crate::sim::{FakeSim, RETRY_TICKS, SASL_DELAY, Value, lock_settings};
+
+/// Scenarios checked in under `scenarios/`, by file name
+pub const SCENARIOS: [(&str, &str); 5] = [
+    ("gpu.txt", include_str!("../scenarios/gpu.txt")),
+    ("radio.txt", include_str!("../scenarios/radio.txt")),
+    (
+        "nosewheel_steering.txt",
+        include_str!("../scenarios/nosewheel_steering.txt"),
+    ),
+    ("hsi.txt", include_str!("../scenarios/hsi.txt")),
+    (
+        "throttle_levers.txt",
+        include_str!("../scenarios/throttle_levers.txt"),
+    ),
+];
+
+fn parse<T: FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("invalid value {text:?}"))
+}
+
+/// `text` in the type of the dataref value `like`
+fn parse_value(like: &Value, text: &str) -> Result<Value, String> {
+    Ok(match like {
+        Value::Int(_) => Value::Int(parse(text)?),
+        Value::Float(_) => Value::Float(parse(text)?),
+        Value::FloatArray(_) => Value::FloatArray(
+            text.split_whitespace()
+                .map(parse)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::String(_) => Value::String(text.to_string()),
+    })
+}
+
+/// Runs the lines of a scenario against a [`FakeSim`]
+struct Runner {
+    sim: FakeSim,
+
+    /// Number of plugin writes before the last `run`
+    writes_before_run: usize,
+}
+
+impl Runner {
+    fn new() -> Result<Self, String> {
+        let mut sim = FakeSim::bae146();
+        while !sim.step() {
+            if sim.tick() > SASL_DELAY + RETRY_TICKS {
+                return Err("components never initialized".into());
+            }
+        }
+        sim.clear_writes();
+
+        Ok(Self {
+            sim,
+            writes_before_run: 0,
+        })
+    }
+
+    /// Current value of `name`, fails for unknown datarefs
+    fn get(&self, name: &str) -> Result<Value, String> {
+        self.sim
+            .try_get(name)
+            .ok_or_else(|| format!("unknown dataref {name}"))
+    }
+
+    fn run(&mut self, loops: u32) {
+        self.writes_before_run = self.sim.writes().len();
+        self.sim.run(loops);
+    }
+
+    /// Values the plugin wrote to `name` during the last `run`
+    fn written(&self, name: &str) -> Vec<Value> {
+        self.sim.writes()[self.writes_before_run..]
+            .iter()
+            .filter(|write| write.name == name)
+            .map(|write| write.value.clone())
+            .collect()
+    }
+
+    fn execute(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.splitn(3, char::is_whitespace);
+        let command = words.next().unwrap_or_default();
+        let argument = words.next().unwrap_or_default();
+        let value = words.next().unwrap_or_default().trim();
+
+        match command {
+            "set" => {
+                let value = parse_value(&self.get(argument)?, value)?;
+                self.sim.set(argument, value);
+            }
+            "run" => self.run(parse(argument)?),
+            "settle" => self.run(RETRY_TICKS),
+            "expect" => {
+                let expected = parse_value(&self.get(argument)?, value)?;
+                let actual = self.get(argument)?;
+                if actual != expected {
+                    return Err(format!(
+                        "{argument} is {actual:?}, expected {expected:?}"
+                    ));
+                }
+            }
+            "wrote" => {
+                let expected = parse_value(&self.get(argument)?, value)?;
+                let written = self.written(argument);
+                if !written.contains(&expected) {
+                    return Err(format!(
+                        "{expected:?} not written to {argument}, \
+                         only {written:?}"
+                    ));
+                }
+            }
+            "unwritten" => {
+                let written = self.written(argument);
+                if !written.is_empty() {
+                    return Err(format!("{argument} written: {written:?}"));
+                }
+            }
+            _ => return Err(format!("unknown command {command:?}")),
+        }
+        Ok(())
+    }
+}
+
+/// Run a scenario against freshly initialized components.
+/// Returns every line that failed as `file:line: reason`.
+///
+/// A scenario is a list of commands, `#` starts a comment:
+/// - `set <dataref> <value>` changes a value as X-Plane would
+/// - `run <loops>` runs flight loops, `settle` a simulated second
+/// - `expect <dataref> <value>` checks the current value
+/// - `wrote <dataref> <value>` checks the plugin wrote the value
+///   during the last `run`, `unwritten <dataref>` that it didn't
+pub fn run(file: &str, text: &str) -> Vec<String> {
+    // Changes to the published settings datarefs reach the global settings
+    let _settings = lock_settings();
+    let current = SETTINGS.lock().map(|settings| settings.clone());
+    let _saved = current.map(SettingsScope::enter);
+
+    let mut failures = Vec::new();
+    match Runner::new() {
+        Ok(mut runner) => {
+            for (index, line) in text.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if let Err(reason) = runner.execute(line) {
+                    failures.push(format!("{file}:{}: {reason}", index + 1));
+                }
+            }
+        }
+        Err(reason) => failures.push(format!("{file}: {reason}")),
+    }
+
+    failures
+}
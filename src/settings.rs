@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+#[cfg(feature = "plugin")]
+use std::fmt::Write;
+#[cfg(feature = "plugin")]
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use crate::component::ComponentInfo;
+use crate::log::Level;
+#[cfg(feature = "plugin")]
+use crate::log::{self, LOG_FILE_NAME};
+#[cfg(feature = "plugin")]
+use crate::plugin::PLUGIN_NAME;
+use crate::plugin::PluginError;
+use crate::recorder::Format;
+
+/// Settings of the running plugin
+pub static SETTINGS: Mutex<Settings> = Mutex::new(Settings::new());
+
+#[cfg(feature = "plugin")]
+static SETTINGS_FILE_NAME: &str = "settings.txt";
+
+/// A number the user can tune in the settings file
+pub struct Tunable<T> {
+    pub key: &'static str,
+    pub default: T,
+    pub min: T,
+    pub max: T,
+}
+
+impl<T: Copy + PartialOrd + FromStr + Display> Tunable<T> {
+    fn parse(&self, value: &str) -> Result<T, String> {
+        let parsed: T = value
+            .parse()
+            .map_err(|_| format!("{value:?} is not a valid {}", self.key))?;
+
+        self.check(parsed)
+    }
+
+    /// Reject values outside of the allowed range
+    pub fn check(&self, value: T) -> Result<T, String> {
+        // Written this way round to reject NaN as well
+        if !(value >= self.min && value <= self.max) {
+            return Err(format!(
+                "{} must be between {} and {}",
+                self.key, self.min, self.max
+            ));
+        }
+
+        Ok(value)
+    }
+}
+
+/// GPU voltage supplied while the aircraft's GPU is connected
+pub const GPU_VOLTS: Tunable<f32> = Tunable {
+    key: "gpu_volts",
+    default: 27.5,
+    min: 0.0,
+    max: 40.0,
+};
+
+/// Bus voltage above which the COM radios get power
+pub const RADIO_MIN_BUS_VOLTS: Tunable<f32> = Tunable {
+    key: "radio_min_bus_volts",
+    default: 21.0,
+    min: 0.0,
+    max: 40.0,
+};
+
+/// Green system pressure (psi) above which nosewheel steering works
+pub const NOSEWHEEL_MIN_PRESSURE: Tunable<f32> = Tunable {
+    key: "nosewheel_min_pressure",
+    default: 100.0,
+    min: 0.0,
+    max: 3000.0,
+};
+
+/// HSI selector value of the RNAV position
+pub const HSI_RNAV_SELECTOR: Tunable<i32> = Tunable {
+    key: "hsi_rnav_selector",
+    default: 2,
+    min: 0,
+    max: 2,
+};
+
+/// Flight loops to wait after enabling before looking for SASL datarefs
+pub const STARTUP_DELAY_LOOPS: Tunable<u32> = Tunable {
+    key: "startup_delay_loops",
+    default: 300,
+    min: 1,
+    max: 10_000,
+};
+
+/// Seconds to wait for a component's datarefs before giving up on it
+pub const INIT_TIMEOUT_SECS: Tunable<f32> = Tunable {
+    key: "init_timeout_secs",
+    default: 120.0,
+    min: 1.0,
+    max: 3600.0,
+};
+
+/// Current values of all tunables
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tunables {
+    pub gpu_volts: f32,
+    pub radio_min_bus_volts: f32,
+    pub nosewheel_min_pressure: f32,
+    pub hsi_rnav_selector: i32,
+    pub startup_delay_loops: u32,
+    pub init_timeout_secs: f32,
+}
+
+impl Tunables {
+    pub const DEFAULT: Self = Self {
+        gpu_volts: GPU_VOLTS.default,
+        radio_min_bus_volts: RADIO_MIN_BUS_VOLTS.default,
+        nosewheel_min_pressure: NOSEWHEEL_MIN_PRESSURE.default,
+        hsi_rnav_selector: HSI_RNAV_SELECTOR.default,
+        startup_delay_loops: STARTUP_DELAY_LOOPS.default,
+        init_timeout_secs: INIT_TIMEOUT_SECS.default,
+    };
+}
+
+/// Tunables of the running plugin
+pub fn tunables() -> Tunables {
+    SETTINGS
+        .try_lock()
+        .map_or(Tunables::DEFAULT, |settings| settings.tunables)
+}
+
+/// Whether component updates are profiled right now
+pub fn profiling() -> bool {
+    SETTINGS
+        .try_lock()
+        .is_ok_and(|settings| settings.profile_updates)
+}
+
+/// Whether throttle sync is on, the default while the settings are busy
+pub fn sync_throttles() -> bool {
+    SETTINGS
+        .try_lock()
+        .map_or(Settings::new().sync_throttles, |settings| {
+            settings.sync_throttles
+        })
+}
+
+/// Settings swapped in until dropped,
+/// the previous ones come back even if the holder panics
+pub struct SettingsScope {
+    previous: Option<Settings>,
+}
+
+impl SettingsScope {
+    pub fn enter(settings: Settings) -> Self {
+        let mut lock = SETTINGS.lock().unwrap_or_else(PoisonError::into_inner);
+        Self {
+            previous: Some(std::mem::replace(&mut *lock, settings)),
+        }
+    }
+}
+
+impl Drop for SettingsScope {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            *SETTINGS.lock().unwrap_or_else(PoisonError::into_inner) =
+                previous;
+        }
+    }
+}
+
+/// User choices that survive a reload of the plugin.
+/// Stored as `key = value` lines in the plugin folder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub sync_throttles: bool,
+    pub tunables: Tunables,
+
+    /// Most verbose log entries written
+    pub log_level: Level,
+
+    /// Also write log entries to `tweaks.log` in the plugin folder
+    pub log_file: bool,
+
+    /// File format of new recordings
+    pub record_format: Format,
+
+    /// Time component updates and count their dataref accesses
+    pub profile_updates: bool,
+
+    /// Enable flags chosen by the user, by component id
+    components: BTreeMap<String, bool>,
+}
+
+impl Settings {
+    pub const fn new() -> Self {
+        Self {
+            sync_throttles: true,
+            tunables: Tunables::DEFAULT,
+            log_level: Level::Info,
+            log_file: false,
+            record_format: Format::Csv,
+            profile_updates: false,
+            components: BTreeMap::new(),
+        }
+    }
+
+    /// Plugin folder holding the platform folders
+    #[cfg(feature = "plugin")]
+    pub fn dir() -> PathBuf {
+        let xpl_path = xplm::plugin::management::this_plugin().path();
+        xpl_path
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("."))
+            .to_path_buf()
+    }
+
+    /// Location of the settings file in the plugin folder
+    #[cfg(feature = "plugin")]
+    pub fn path() -> PathBuf {
+        Self::dir().join(SETTINGS_FILE_NAME)
+    }
+
+    /// Apply the log settings to the logger
+    #[cfg(feature = "plugin")]
+    pub fn configure_log(&self) {
+        let log_file = self.log_file.then(|| Self::dir().join(LOG_FILE_NAME));
+        log::configure(self.log_level, log_file.as_deref());
+    }
+
+    /// Load settings from `path`.
+    /// Falls back to defaults if the file is missing or corrupt.
+    #[cfg(feature = "plugin")]
+    pub fn load(path: &Path) -> Self {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                info!(
+                    "no settings file at {}, using defaults",
+                    path.display()
+                );
+                return Self::new();
+            }
+            Err(error) => {
+                warn!(
+                    "unable to read {}, using defaults: {error}",
+                    path.display()
+                );
+                return Self::new();
+            }
+        };
+
+        match Self::parse(&text) {
+            Ok(settings) => {
+                info!("loaded {}", path.display());
+                settings
+            }
+            Err(error) => {
+                warn!(
+                    "{} is corrupt, using defaults: {error}",
+                    path.display()
+                );
+                Self::new()
+            }
+        }
+    }
+
+    /// Write settings to `path`, logging any error
+    #[cfg(feature = "plugin")]
+    pub fn save(&self, path: &Path) {
+        if let Err(error) = std::fs::write(path, self.to_text()) {
+            warn!("unable to write {}: {error}", path.display());
+        }
+    }
+
+    pub fn component_enabled(&self, info: &ComponentInfo) -> bool {
+        self.components
+            .get(info.id)
+            .copied()
+            .unwrap_or(info.enabled_by_default)
+    }
+
+    pub fn set_component_enabled(&mut self, id: &str, enabled: bool) {
+        self.components.insert(id.to_string(), enabled);
+    }
+
+    pub fn parse(text: &str) -> Result<Self, PluginError> {
+        let mut settings = Self::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |reason: String| PluginError::InvalidSetting {
+                line: index + 1,
+                reason,
+            };
+            let parse_bool = |value: &str| {
+                value
+                    .parse::<bool>()
+                    .map_err(|_| invalid(format!("{value:?} is not a bool")))
+            };
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected key = value".to_string()))?;
+            let (key, value) = (key.trim(), value.trim());
+            let tunables = &mut settings.tunables;
+
+            if key == "sync_throttles" {
+                settings.sync_throttles = parse_bool(value)?;
+            } else if key == "log_level" {
+                settings.log_level = value.parse().map_err(invalid)?;
+            } else if key == "log_file" {
+                settings.log_file = parse_bool(value)?;
+            } else if key == "record_format" {
+                settings.record_format = value.parse().map_err(invalid)?;
+            } else if key == "profile_updates" {
+                settings.profile_updates = parse_bool(value)?;
+            } else if let Some(id) = key.strip_prefix("component.") {
+                let enabled = parse_bool(value)?;
+                settings.set_component_enabled(id, enabled);
+            } else if key == GPU_VOLTS.key {
+                tunables.gpu_volts =
+                    GPU_VOLTS.parse(value).map_err(invalid)?;
+            } else if key == RADIO_MIN_BUS_VOLTS.key {
+                tunables.radio_min_bus_volts =
+                    RADIO_MIN_BUS_VOLTS.parse(value).map_err(invalid)?;
+            } else if key == NOSEWHEEL_MIN_PRESSURE.key {
+                tunables.nosewheel_min_pressure =
+                    NOSEWHEEL_MIN_PRESSURE.parse(value).map_err(invalid)?;
+            } else if key == HSI_RNAV_SELECTOR.key {
+                tunables.hsi_rnav_selector =
+                    HSI_RNAV_SELECTOR.parse(value).map_err(invalid)?;
+            } else if key == STARTUP_DELAY_LOOPS.key {
+                tunables.startup_delay_loops =
+                    STARTUP_DELAY_LOOPS.parse(value).map_err(invalid)?;
+            } else if key == INIT_TIMEOUT_SECS.key {
+                tunables.init_timeout_secs =
+                    INIT_TIMEOUT_SECS.parse(value).map_err(invalid)?;
+            } else {
+                // Probably written by a newer version of the plugin
+                warn!("ignoring unknown setting {key}");
+            }
+        }
+
+        Ok(settings)
+    }
+
+    #[cfg(feature = "plugin")]
+    pub fn to_text(&self) -> String {
+        let tunables = &self.tunables;
+        let mut text = format!(
+            "# {PLUGIN_NAME} settings\nsync_throttles = {}\n",
+            self.sync_throttles
+        );
+        let _ = writeln!(text, "log_level = {}", self.log_level);
+        let _ = writeln!(text, "log_file = {}", self.log_file);
+        let _ = writeln!(text, "record_format = {}", self.record_format);
+        let _ = writeln!(text, "profile_updates = {}", self.profile_updates);
+        let _ = writeln!(text, "{} = {}", GPU_VOLTS.key, tunables.gpu_volts);
+        let _ = writeln!(
+            text,
+            "{} = {}",
+            RADIO_MIN_BUS_VOLTS.key, tunables.radio_min_bus_volts
+        );
+        let _ = writeln!(
+            text,
+            "{} = {}",
+            NOSEWHEEL_MIN_PRESSURE.key, tunables.nosewheel_min_pressure
+        );
+        let _ = writeln!(
+            text,
+            "{} = {}",
+            HSI_RNAV_SELECTOR.key, tunables.hsi_rnav_selector
+        );
+        let _ = writeln!(
+            text,
+            "{} = {}",
+            STARTUP_DELAY_LOOPS.key, tunables.startup_delay_loops
+        );
+        let _ = writeln!(
+            text,
+            "{} = {}",
+            INIT_TIMEOUT_SECS.key, tunables.init_timeout_secs
+        );
+        for (id, enabled) in &self.components {
+            let _ = writeln!(text, "component.{id} = {enabled}");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+
+    #[test]
+    #[cfg(feature = "plugin")]
+    fn settings_survive_round_trip() {
+        use crate::log::Level;
+        use crate::recorder::Format;
+
+        let mut settings = Settings::new();
+        settings.sync_throttles = false;
+        settings.log_level = Level::Trace;
+        settings.log_file = true;
+        settings.record_format = Format::Binary;
+        settings.profile_updates = true;
+        settings.tunables.gpu_volts = 28.0;
+        settings.tunables.startup_delay_loops = 10;
+        settings.set_component_enabled("radio", false);
+        settings.set_component_enabled("gpu", true);
+
+        assert_eq!(Settings::parse(&settings.to_text()).unwrap(), settings);
+    }
+
+    #[test]
+    fn corrupt_settings_are_rejected() {
+        assert!(Settings::parse("sync_throttles = maybe").is_err());
+        assert!(Settings::parse("sync_throttles").is_err());
+        assert!(Settings::parse("gpu_volts = 400").is_err());
+        assert!(Settings::parse("gpu_volts = NaN").is_err());
+        assert!(Settings::parse("hsi_rnav_selector = 1.5").is_err());
+        assert!(Settings::parse("# comment\n\nunknown = true").is_ok());
+    }
+}
@@ -0,0 +1,479 @@
+use std::cell::RefCell;
+use std::ffi::{CString, c_int};
+use std::fmt::Display;
+use std::ops::{Add, Sub};
+use std::ptr;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use xplm_sys::{
+    XPAddWidgetCallback, XPBringRootWidgetToFront, XPCreateWidget,
+    XPDestroyWidget, XPGetWidgetProperty, XPHideWidget, XPIsWidgetVisible,
+    XPSetWidgetDescriptor, XPSetWidgetProperty, XPShowWidget, XPWidgetClass,
+    XPWidgetID, XPWidgetMessage,
+};
+
+use crate::menu::{MenuCheckItems, Toggle};
+use crate::registry::ComponentRegistry;
+use crate::settings::{
+    GPU_VOLTS, HSI_RNAV_SELECTOR, INIT_TIMEOUT_SECS, NOSEWHEEL_MIN_PRESSURE,
+    RADIO_MIN_BUS_VOLTS, SETTINGS, STARTUP_DELAY_LOOPS, Settings, Tunable,
+    Tunables,
+};
+
+/// A line of the settings window the user can change
+pub trait Field {
+    /// Texts of the lower and the higher button
+    fn buttons(&self) -> [&'static str; 2] {
+        ["[-]", "[+]"]
+    }
+
+    /// `key = value` as in the settings file
+    fn show(&self, settings: &Settings) -> String;
+
+    /// Apply a click on the higher (`up`) or lower button.
+    /// Returns whether the value changed.
+    fn adjust(&self, settings: &mut Settings, up: bool) -> bool;
+}
+
+/// Throttle sync, switched off or on by the buttons
+pub struct SyncThrottles;
+
+impl Field for SyncThrottles {
+    fn buttons(&self) -> [&'static str; 2] {
+        ["off", "on"]
+    }
+
+    fn show(&self, settings: &Settings) -> String {
+        let state = if settings.sync_throttles { "on" } else { "off" };
+        format!("sync_throttles = {state}")
+    }
+
+    fn adjust(&self, settings: &mut Settings, up: bool) -> bool {
+        let changed = settings.sync_throttles != up;
+        settings.sync_throttles = up;
+        changed
+    }
+}
+
+/// Tunable moved by a fixed step, stopping at its limits
+pub struct Stepper<T: 'static> {
+    pub tunable: &'static Tunable<T>,
+    pub step: T,
+    pub value: fn(&mut Tunables) -> &mut T,
+}
+
+impl<T> Field for Stepper<T>
+where
+    T: Copy
+        + PartialOrd
+        + FromStr
+        + Display
+        + Add<Output = T>
+        + Sub<Output = T>,
+{
+    fn show(&self, settings: &Settings) -> String {
+        let mut tunables = settings.tunables;
+        format!("{} = {}", self.tunable.key, (self.value)(&mut tunables))
+    }
+
+    fn adjust(&self, settings: &mut Settings, up: bool) -> bool {
+        let Tunable { min, max, .. } = *self.tunable;
+        let value = (self.value)(&mut settings.tunables);
+        let old = *value;
+        // Compare the distance first, unsigned values can't go below zero
+        *value = if up {
+            if max - old < self.step {
+                max
+            } else {
+                old + self.step
+            }
+        } else if old - min < self.step {
+            min
+        } else {
+            old - self.step
+        };
+        *value != old
+    }
+}
+
+/// Every line of the settings window, top to bottom
+pub const FIELDS: [&dyn Field; 7] = [
+    &SyncThrottles,
+    &Stepper {
+        tunable: &GPU_VOLTS,
+        step: 0.5,
+        value: |tunables| &mut tunables.gpu_volts,
+    },
+    &Stepper {
+        tunable: &RADIO_MIN_BUS_VOLTS,
+        step: 0.5,
+        value: |tunables| &mut tunables.radio_min_bus_volts,
+    },
+    &Stepper {
+        tunable: &NOSEWHEEL_MIN_PRESSURE,
+        step: 10.0,
+        value: |tunables| &mut tunables.nosewheel_min_pressure,
+    },
+    &Stepper {
+        tunable: &HSI_RNAV_SELECTOR,
+        step: 1,
+        value: |tunables| &mut tunables.hsi_rnav_selector,
+    },
+    &Stepper {
+        tunable: &STARTUP_DELAY_LOOPS,
+        step: 50,
+        value: |tunables| &mut tunables.startup_delay_loops,
+    },
+    &Stepper {
+        tunable: &INIT_TIMEOUT_SECS,
+        step: 10.0,
+        value: |tunables| &mut tunables.init_timeout_secs,
+    },
+];
+
+/// Height of a line of widgets
+const ROW_HEIGHT: i32 = 22;
+
+/// Width of the buttons in front of each line
+const BUTTON_WIDTH: i32 = 40;
+
+/// Space for the title bar of the window
+const TITLE_HEIGHT: i32 = 20;
+
+/// Space between the window border and the widgets
+const MARGIN: i32 = 10;
+
+/// Width of the window
+const WIDTH: i32 = 360;
+
+/// Standard widget ids as the types the widget functions take,
+/// bindgen gives the C enums a different type on each platform
+#[allow(clippy::cast_possible_wrap)]
+mod ids {
+    use xplm_sys::{
+        XPWidgetClass, XPWidgetMessage, XPWidgetPropertyID,
+        xpButtonBehaviorPushButton, xpMessage_CloseButtonPushed, xpMsg_Draw,
+        xpMsg_PushButtonPressed, xpProperty_ButtonBehavior,
+        xpProperty_ButtonType, xpProperty_MainWindowHasCloseBoxes,
+        xpProperty_Refcon, xpPushButton, xpWidgetClass_Button,
+        xpWidgetClass_Caption, xpWidgetClass_MainWindow,
+    };
+
+    pub const MAIN_WINDOW: XPWidgetClass =
+        xpWidgetClass_MainWindow as XPWidgetClass;
+    pub const BUTTON: XPWidgetClass = xpWidgetClass_Button as XPWidgetClass;
+    pub const CAPTION: XPWidgetClass = xpWidgetClass_Caption as XPWidgetClass;
+
+    pub const REFCON: XPWidgetPropertyID =
+        xpProperty_Refcon as XPWidgetPropertyID;
+    pub const HAS_CLOSE_BOXES: XPWidgetPropertyID =
+        xpProperty_MainWindowHasCloseBoxes as XPWidgetPropertyID;
+    pub const BUTTON_TYPE: XPWidgetPropertyID =
+        xpProperty_ButtonType as XPWidgetPropertyID;
+    pub const BUTTON_BEHAVIOR: XPWidgetPropertyID =
+        xpProperty_ButtonBehavior as XPWidgetPropertyID;
+    pub const PUSH_BUTTON: isize = xpPushButton as isize;
+    pub const PUSH_BUTTON_BEHAVIOR: isize =
+        xpButtonBehaviorPushButton as isize;
+
+    pub const DRAW: XPWidgetMessage = xpMsg_Draw as XPWidgetMessage;
+    pub const PUSH_BUTTON_PRESSED: XPWidgetMessage =
+        xpMsg_PushButtonPressed as XPWidgetMessage;
+    pub const CLOSE_BUTTON_PUSHED: XPWidgetMessage =
+        xpMessage_CloseButtonPushed as XPWidgetMessage;
+}
+
+/// Widgets of one [`Field`]
+struct Line {
+    lower: XPWidgetID,
+    higher: XPWidgetID,
+    caption: XPWidgetID,
+}
+
+/// What the widget callback works with, the refcon of the window
+struct Controls {
+    lines: Vec<Line>,
+    /// Caption texts as last set, only changed ones are updated
+    shown: RefCell<Vec<String>>,
+    components: Rc<RefCell<ComponentRegistry>>,
+    check_items: Rc<MenuCheckItems>,
+}
+
+impl Controls {
+    /// Apply a click to the running plugin and the settings file.
+    /// Returns whether `button` belongs to a [`Field`].
+    fn click(&self, button: XPWidgetID) -> bool {
+        let Some((field, up)) =
+            FIELDS.iter().zip(&self.lines).find_map(|(field, line)| {
+                if button == line.lower {
+                    Some((*field, false))
+                } else if button == line.higher {
+                    Some((*field, true))
+                } else {
+                    None
+                }
+            })
+        else {
+            return false;
+        };
+
+        if let Ok(mut settings) = SETTINGS.lock()
+            && field.adjust(&mut settings, up)
+        {
+            info!("settings window: {}", field.show(&settings));
+            settings.save(&Settings::path());
+        }
+        if let Ok(components) = self.components.try_borrow() {
+            self.check_items.refresh(&components);
+        }
+        true
+    }
+
+    /// Show the current settings in the captions
+    fn refresh(&self) {
+        let Ok(settings) = SETTINGS.try_lock() else {
+            return;
+        };
+        let mut shown = self.shown.borrow_mut();
+        for ((field, line), shown) in
+            FIELDS.iter().zip(&self.lines).zip(shown.iter_mut())
+        {
+            let text = field.show(&settings);
+            if *shown != text {
+                let descriptor =
+                    CString::new(text.as_str()).unwrap_or_default();
+                // SAFETY: the caption lives as long as the window,
+                // X-Plane copies the descriptor
+                unsafe {
+                    XPSetWidgetDescriptor(line.caption, descriptor.as_ptr());
+                }
+                *shown = text;
+            }
+        }
+    }
+}
+
+/// Widget callback of the settings window
+unsafe extern "C" fn handle_message(
+    message: XPWidgetMessage,
+    window: XPWidgetID,
+    param1: isize,
+    _param2: isize,
+) -> c_int {
+    // SAFETY: the refcon is set to the controls of the window
+    // before the callback is added and they live as long as the window
+    let controls = unsafe {
+        let refcon = XPGetWidgetProperty(window, ids::REFCON, ptr::null_mut());
+        (refcon as *const Controls).as_ref()
+    };
+    let Some(controls) = controls else {
+        return 0;
+    };
+
+    let handled = match message {
+        ids::DRAW => {
+            controls.refresh();
+            false
+        }
+        ids::PUSH_BUTTON_PRESSED => controls.click(param1 as XPWidgetID),
+        ids::CLOSE_BUTTON_PUSHED => {
+            // SAFETY: the window is the one the message is for
+            unsafe { XPHideWidget(window) };
+            true
+        }
+        _ => false,
+    };
+    c_int::from(handled)
+}
+
+/// Widget inside `window`, a line high with its top left corner at
+/// `left`, `top`
+fn create_child(
+    window: XPWidgetID,
+    class: XPWidgetClass,
+    [left, top]: [i32; 2],
+    width: i32,
+    text: &str,
+) -> XPWidgetID {
+    let text = CString::new(text).unwrap_or_default();
+    // SAFETY: `window` is a live widget, X-Plane copies the descriptor
+    unsafe {
+        XPCreateWidget(
+            left,
+            top,
+            left + width,
+            top - ROW_HEIGHT + 2,
+            1,
+            text.as_ptr(),
+            0,
+            window,
+            class,
+        )
+    }
+}
+
+/// Push button inside `window`
+fn create_button(
+    window: XPWidgetID,
+    position: [i32; 2],
+    text: &str,
+) -> XPWidgetID {
+    let button =
+        create_child(window, ids::BUTTON, position, BUTTON_WIDTH, text);
+    // SAFETY: the button was just created
+    unsafe {
+        XPSetWidgetProperty(button, ids::BUTTON_TYPE, ids::PUSH_BUTTON);
+        XPSetWidgetProperty(
+            button,
+            ids::BUTTON_BEHAVIOR,
+            ids::PUSH_BUTTON_BEHAVIOR,
+        );
+    }
+    button
+}
+
+/// Widget window with a line of buttons per [`Field`],
+/// hidden until opened from the menu or by command
+pub struct SettingsWindow {
+    window: XPWidgetID,
+    /// Owned here, the window's refcon points to it
+    controls: *mut Controls,
+}
+
+impl SettingsWindow {
+    pub fn new(
+        components: Rc<RefCell<ComponentRegistry>>,
+        check_items: Rc<MenuCheckItems>,
+    ) -> Self {
+        let (left, top) = (100, 400);
+        let bottom = FIELDS
+            .iter()
+            .fold(top - TITLE_HEIGHT - 2 * MARGIN, |y, _| y - ROW_HEIGHT);
+
+        // SAFETY: creates a new root widget, X-Plane copies the title
+        let window = unsafe {
+            let window = XPCreateWidget(
+                left,
+                top,
+                left + WIDTH,
+                bottom,
+                0,
+                c"BAe 146 Tweaks settings".as_ptr(),
+                1,
+                ptr::null_mut(),
+                ids::MAIN_WINDOW,
+            );
+            XPSetWidgetProperty(window, ids::HAS_CLOSE_BOXES, 1);
+            window
+        };
+
+        let mut y = top - TITLE_HEIGHT - MARGIN;
+        let lines = FIELDS
+            .iter()
+            .map(|field| {
+                let [lower, higher] = field.buttons();
+                let x = left + MARGIN;
+                let line = Line {
+                    lower: create_button(window, [x, y], lower),
+                    higher: create_button(
+                        window,
+                        [x + BUTTON_WIDTH, y],
+                        higher,
+                    ),
+                    caption: create_child(
+                        window,
+                        ids::CAPTION,
+                        [x + 2 * BUTTON_WIDTH + MARGIN, y],
+                        WIDTH - 2 * BUTTON_WIDTH - 3 * MARGIN,
+                        "",
+                    ),
+                };
+                y -= ROW_HEIGHT;
+                line
+            })
+            .collect();
+
+        let controls = Box::into_raw(Box::new(Controls {
+            lines,
+            shown: RefCell::new(vec![String::new(); FIELDS.len()]),
+            components,
+            check_items,
+        }));
+        // SAFETY: the controls are freed only after the window is destroyed
+        unsafe {
+            XPSetWidgetProperty(window, ids::REFCON, controls as isize);
+            XPAddWidgetCallback(window, Some(handle_message));
+        }
+        Self { window, controls }
+    }
+}
+
+impl Toggle for SettingsWindow {
+    fn visible(&self) -> bool {
+        // SAFETY: the window lives until `self` is dropped
+        unsafe { XPIsWidgetVisible(self.window) != 0 }
+    }
+
+    fn set_visible(&self, visible: bool) {
+        // SAFETY: the window lives until `self` is dropped
+        unsafe {
+            if visible {
+                XPShowWidget(self.window);
+                XPBringRootWidgetToFront(self.window);
+            } else {
+                XPHideWidget(self.window);
+            }
+        }
+    }
+}
+
+impl Drop for SettingsWindow {
+    fn drop(&mut self) {
+        // SAFETY: no callback runs once the widgets are destroyed,
+        // the controls came from `Box::into_raw`
+        unsafe {
+            XPDestroyWidget(self.window, 1);
+            drop(Box::from_raw(self.controls));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FIELDS;
+    use crate::settings::{
+        GPU_VOLTS, STARTUP_DELAY_LOOPS, Settings, Tunables,
+    };
+
+    #[test]
+    fn steps_stop_at_the_limits() {
+        let mut settings = Settings::new();
+        settings.tunables.gpu_volts = GPU_VOLTS.max - 0.2;
+
+        let gpu_volts = FIELDS[1];
+        assert!(gpu_volts.adjust(&mut settings, true));
+        assert_eq!(gpu_volts.show(&settings), "gpu_volts = 40");
+        assert!(!gpu_volts.adjust(&mut settings, true));
+        assert!(gpu_volts.adjust(&mut settings, false));
+        assert_eq!(gpu_volts.show(&settings), "gpu_volts = 39.5");
+
+        let startup_delay_loops = FIELDS[5];
+        settings.tunables.startup_delay_loops = 20;
+        assert!(startup_delay_loops.adjust(&mut settings, false));
+        assert_eq!(
+            settings.tunables.startup_delay_loops,
+            STARTUP_DELAY_LOOPS.min
+        );
+    }
+
+    #[test]
+    fn sync_throttles_buttons_switch_off_and_on() {
+        let mut settings = Settings::new();
+        assert!(FIELDS[0].adjust(&mut settings, false));
+        assert!(!FIELDS[0].adjust(&mut settings, false));
+        assert_eq!(FIELDS[0].show(&settings), "sync_throttles = off");
+        assert!(FIELDS[0].adjust(&mut settings, true));
+        assert_eq!(settings.tunables, Tunables::DEFAULT);
+    }
+}
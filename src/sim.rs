@@ -0,0 +1,756 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::aircraft::{Aircraft, Compatibility, Variant};
+use crate::component::PluginComponent;
+use crate::dataref::{
+    ArrayRead, ArrayReadWrite, DataRefProvider, ScalarRead, ScalarReadWrite,
+    StringRead,
+};
+use crate::diagnostics;
+use crate::handler::{FlightLoopHandler, INIT_RETRY_INTERVAL, NextCall};
+use crate::messaging::{self, Notifier, Request};
+use crate::plugin::PluginError;
+use crate::profile::{self, CountingDataRefs};
+use crate::published::PublishedDataRefs;
+use crate::recorder::{self, Format, Recorder, RecordingDataRefs};
+use crate::registry::{ComponentRegistry, InitState};
+use crate::settings::{Settings, tunables};
+
+/// Number of ticks until the SASL datarefs of the aircraft show up
+pub const SASL_DELAY: u32 = 10;
+
+/// Aircraft the components are created for
+pub const AIRCRAFT: Aircraft = Aircraft {
+    variant: Variant::B462,
+    version: None,
+};
+
+/// Simulated time between two ticks
+pub const FRAME_TIME: Duration = Duration::from_millis(50);
+
+/// Ticks between two initialization attempts
+pub const RETRY_TICKS: u32 = ticks(INIT_RETRY_INTERVAL);
+
+/// Ticks until `delay` has passed
+#[allow(clippy::cast_possible_truncation)]
+const fn ticks(delay: Duration) -> u32 {
+    delay.as_millis().div_ceil(FRAME_TIME.as_millis()) as u32
+}
+
+/// Held by tests which change the global settings
+/// and by those which rely on the defaults while running
+static SETTINGS_USERS: Mutex<()> = Mutex::new(());
+
+pub fn lock_settings() -> MutexGuard<'static, ()> {
+    SETTINGS_USERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A state change the plugin told other plugins about
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub id: String,
+    pub state: i32,
+    pub suspended: Duration,
+}
+
+/// Records notifications instead of sending them
+struct FakeNotifier {
+    notifications: Rc<RefCell<Vec<Notification>>>,
+
+    /// Other plugins may message the plugin while being notified
+    components: Rc<RefCell<ComponentRegistry>>,
+}
+
+impl Notifier for FakeNotifier {
+    fn state_changed(&self, id: &str, state: i32, suspended: Duration) {
+        assert!(
+            self.components.try_borrow_mut().is_ok(),
+            "components still borrowed while notifying"
+        );
+        self.notifications.borrow_mut().push(Notification {
+            id: id.to_string(),
+            state,
+            suspended,
+        });
+    }
+}
+
+/// Recording kept in memory
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+}
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Value of a dataref in the [`FakeSim`] table
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Float(f32),
+    FloatArray(Vec<f32>),
+    String(String),
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl<const N: usize> From<[f32; N]> for Value {
+    fn from(value: [f32; N]) -> Self {
+        Self::FloatArray(value.to_vec())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+/// A write made by the plugin
+#[derive(Clone, Debug, PartialEq)]
+pub struct Write {
+    pub tick: u32,
+    pub name: String,
+    pub value: Value,
+}
+
+struct Entry {
+    value: Value,
+    writeable: bool,
+    /// Tick at which the dataref can be found
+    available_at: u32,
+}
+
+#[derive(Default)]
+struct Table {
+    tick: u32,
+    entries: HashMap<String, Entry>,
+    writes: Vec<Write>,
+    /// Number of lookups by dataref name
+    lookups: HashMap<String, u32>,
+}
+
+impl Table {
+    fn get(&self, name: &str) -> &Value {
+        &self.entries[name].value
+    }
+
+    fn set(&mut self, name: &str, value: Value) {
+        if let Some(entry) = self.entries.get_mut(name) {
+            entry.value = value.clone();
+        }
+        self.writes.push(Write {
+            tick: self.tick,
+            name: name.to_string(),
+            value,
+        });
+    }
+}
+
+/// In-memory stand-in for X-Plane.
+/// Hosts a dataref table and drives the [`FlightLoopHandler`]
+/// one flight loop at a time.
+pub struct FakeSim {
+    table: Rc<RefCell<Table>>,
+    components: Rc<RefCell<ComponentRegistry>>,
+    handler: FlightLoopHandler,
+    recorder: Rc<RefCell<Recorder>>,
+    notifications: Rc<RefCell<Vec<Notification>>>,
+    scheduled: Vec<(u32, String, Value)>,
+
+    /// Tick the flight loop runs next, as scheduled by the handler
+    next_call: u32,
+    /// Tick the flight loop ran last
+    last_call: Option<u32>,
+    /// Whether every component was initialized after the last run
+    initialized: bool,
+}
+
+impl FakeSim {
+    /// Create a simulator without any datarefs
+    pub fn empty() -> Self {
+        let table = Rc::new(RefCell::new(Table::default()));
+        let recorder = Rc::new(RefCell::new(Recorder::new()));
+        let accesses = Rc::new(Cell::default());
+        let data = Rc::new(CountingDataRefs::new(
+            RecordingDataRefs::new(
+                FakeDataRefs(Rc::clone(&table)),
+                Rc::clone(&recorder),
+            ),
+            Rc::clone(&accesses),
+        ));
+        let components =
+            Rc::new(RefCell::new(ComponentRegistry::with_components(
+                Rc::clone(&data) as _,
+                AIRCRAFT,
+            )));
+        let published =
+            PublishedDataRefs::new(data.as_ref(), &components.borrow())
+                .expect("plugin datarefs already exist");
+        let notifications = Rc::new(RefCell::new(Vec::new()));
+        let handler = FlightLoopHandler::new(Rc::clone(&components))
+            .with_published(published)
+            .with_notifier(FakeNotifier {
+                notifications: Rc::clone(&notifications),
+                components: Rc::clone(&components),
+            })
+            .with_recorder(Rc::clone(&recorder))
+            .with_profiling(accesses);
+        // Creating the plugin's own datarefs is not interesting
+        table.borrow_mut().writes.clear();
+
+        Self {
+            table,
+            components,
+            handler,
+            recorder,
+            notifications,
+            scheduled: Vec::new(),
+            next_call: 0,
+            last_call: None,
+            initialized: false,
+        }
+    }
+
+    /// Create a simulator with all datarefs used by the components.
+    /// The `thranda/...` datarefs appear after [`SASL_DELAY`] ticks.
+    pub fn bae146() -> Self {
+        let mut sim = Self::empty();
+
+        sim.define("sim/cockpit2/electrical/bus_volts", [0.0_f32; 6], false);
+        sim.define("sim/cockpit2/electrical/GPU_generator_volts", 0.0, true);
+        sim.define("sim/operation/override/override_GPU_volts", 0, true);
+        sim.define(
+            "sim/cockpit2/hydraulics/indicators/hydraulic_pressure_2",
+            0.0,
+            false,
+        );
+        sim.define("sim/operation/override/override_wheel_steer", 0, true);
+        sim.define("sim/cockpit2/radios/actuators/gps_power", 0, false);
+        sim.define("sim/cockpit2/radios/actuators/gps2_power", 0, false);
+        sim.define("sim/cockpit2/radios/actuators/com1_power", 0, true);
+        sim.define("sim/cockpit2/radios/actuators/com2_power", 0, true);
+        sim.define("sim/cockpit/switches/HSI_selector", 0, false);
+        sim.define("sim/cockpit/switches/HSI_selector2", 0, false);
+        sim.define(
+            "sim/cockpit2/radios/actuators/hsi_obs_deg_mag_pilot",
+            0.0,
+            false,
+        );
+        sim.define(
+            "sim/cockpit2/radios/actuators/hsi_obs_deg_mag_copilot",
+            0.0,
+            true,
+        );
+        sim.define(
+            "sim/cockpit2/engine/actuators/throttle_ratio",
+            [0.0_f32; 4],
+            true,
+        );
+        sim.define("sim/aircraft/view/acf_ICAO", "B462", false);
+
+        sim.define_after(
+            "thranda/electrical/ExtPwrGPUAvailable",
+            0,
+            false,
+            SASL_DELAY,
+        );
+        sim.define_after(
+            "thranda/generic/com1/genCom1Pwr",
+            0,
+            false,
+            SASL_DELAY,
+        );
+        sim.define_after(
+            "thranda/generic/com1/genCom2Pwr",
+            0,
+            false,
+            SASL_DELAY,
+        );
+        sim.define_after(
+            "thranda/anim/hsiHdefDotsPilot",
+            0.0,
+            false,
+            SASL_DELAY,
+        );
+        sim.define_after(
+            "thranda/anim/hsiHdefDotsCoPilot",
+            0.0,
+            true,
+            SASL_DELAY,
+        );
+
+        sim
+    }
+
+    /// Add a dataref that can be found right away
+    pub fn define(
+        &mut self,
+        name: &str,
+        value: impl Into<Value>,
+        writeable: bool,
+    ) {
+        self.define_after(name, value, writeable, 0);
+    }
+
+    /// Add a dataref that can only be found from tick `available_at` on
+    pub fn define_after(
+        &mut self,
+        name: &str,
+        value: impl Into<Value>,
+        writeable: bool,
+        available_at: u32,
+    ) {
+        self.table.borrow_mut().entries.insert(
+            name.to_string(),
+            Entry {
+                value: value.into(),
+                writeable,
+                available_at,
+            },
+        );
+    }
+
+    /// Remove a dataref, e.g. to simulate a renamed SASL dataref
+    pub fn remove(&mut self, name: &str) {
+        self.table.borrow_mut().entries.remove(name);
+    }
+
+    /// Change a dataref value as X-Plane or another plugin would
+    pub fn set(&mut self, name: &str, value: impl Into<Value>) {
+        let mut table = self.table.borrow_mut();
+        let entry = table
+            .entries
+            .get_mut(name)
+            .unwrap_or_else(|| panic!("unknown dataref {name}"));
+        entry.value = value.into();
+    }
+
+    /// Change a dataref value once the simulator reaches `tick`
+    pub fn set_at(&mut self, tick: u32, name: &str, value: impl Into<Value>) {
+        self.scheduled.push((tick, name.to_string(), value.into()));
+    }
+
+    pub fn get(&self, name: &str) -> Value {
+        self.table.borrow().get(name).clone()
+    }
+
+    /// Value of `name`, `None` for unknown datarefs
+    pub fn try_get(&self, name: &str) -> Option<Value> {
+        let table = self.table.borrow();
+        table.entries.get(name).map(|entry| entry.value.clone())
+    }
+
+    /// Current tick, i.e. the number of flight loops run so far
+    pub fn tick(&self) -> u32 {
+        self.table.borrow().tick
+    }
+
+    /// Tick the flight loop ran at last
+    pub fn last_call(&self) -> Option<u32> {
+        self.last_call
+    }
+
+    /// Run one simulator frame, the flight loop runs if it is due.
+    /// Returns `true` once every component is initialized.
+    pub fn step(&mut self) -> bool {
+        let tick = self.tick();
+        let (due, pending) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition(|(at, _, _)| *at <= tick);
+        self.scheduled = pending;
+        for (_, name, value) in due {
+            self.set(&name, value);
+        }
+
+        if tick >= self.next_call {
+            let frames = self.last_call.map_or(1, |last| tick - last);
+            let next = self.handler.run_loop(FRAME_TIME * frames);
+            self.initialized = self.handler.initialized();
+            self.last_call = Some(tick);
+            self.next_call = match next {
+                NextCall::NextLoop => tick + 1,
+                NextCall::After(delay) => tick + ticks(delay),
+            };
+        }
+        self.table.borrow_mut().tick += 1;
+        self.initialized
+    }
+
+    /// Update profile as exported by command
+    pub fn profile(&self) -> String {
+        let mut csv = Vec::new();
+        profile::write_csv(&self.components.borrow(), &mut csv)
+            .expect("profile written to memory");
+        String::from_utf8(csv).expect("profile is UTF-8")
+    }
+
+    /// Lines of the diagnostics window
+    pub fn report(&self) -> Vec<String> {
+        diagnostics::report(&mut self.components.borrow_mut())
+    }
+
+    /// Add a component after the ones of the plugin
+    pub fn register(&mut self, component: impl PluginComponent + 'static) {
+        self.components.borrow_mut().register(component);
+    }
+
+    /// Enable or disable a component as the plugin menu would
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) {
+        assert!(
+            self.components.borrow_mut().set_enabled(id, enabled),
+            "unknown component {id}"
+        );
+    }
+
+    /// Send a message as another plugin would,
+    /// returns the state the plugin answers with
+    pub fn message(&mut self, request: Request, id: &str) -> i32 {
+        messaging::handle(&mut self.components.borrow_mut(), request, id)
+    }
+
+    /// Start recording into memory as the plugin menu would
+    pub fn start_recording(&mut self, format: Format) -> SharedBuffer {
+        let buffer = SharedBuffer::default();
+        let header = recorder::header(AIRCRAFT, &Settings::new());
+        self.recorder
+            .borrow_mut()
+            .start(Box::new(buffer.clone()), format, &header)
+            .expect("recording header written");
+        buffer
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder.borrow_mut().stop();
+    }
+
+    /// State changes sent to other plugins so far
+    pub fn notifications(&self) -> Vec<Notification> {
+        self.notifications.borrow().clone()
+    }
+
+    /// Dataref lookups as the components see them
+    pub fn data(&self) -> Rc<dyn DataRefProvider> {
+        Rc::new(FakeDataRefs(Rc::clone(&self.table)))
+    }
+
+    /// Detect the aircraft and switch off fixes as plugin start would.
+    /// Components initialized afterwards see the detected aircraft.
+    pub fn detect_aircraft(
+        &mut self,
+        table: &[Compatibility],
+    ) -> Result<Aircraft, PluginError> {
+        let aircraft =
+            Aircraft::detect(self.data().as_ref(), Path::new("/nonexistent"))?;
+        let mut components = self.components.borrow_mut();
+        components.set_aircraft(aircraft);
+        if let Some(version) = aircraft.version {
+            components.apply_compatibility(table, version);
+        }
+        Ok(aircraft)
+    }
+
+    pub fn init_state(&self, id: &str) -> InitState {
+        self.components
+            .borrow()
+            .get(id)
+            .unwrap_or_else(|| panic!("unknown component {id}"))
+            .init
+            .clone()
+    }
+
+    /// Run a one-shot action as its command would
+    pub fn run_action(&mut self, id: &str, action: &str) -> bool {
+        self.components.borrow_mut().run_action(id, action)
+    }
+
+    /// Stop the flight loop and restore overrides
+    /// as disabling the plugin would
+    pub fn teardown(&mut self) {
+        self.next_call = u32::MAX;
+        self.components.borrow_mut().teardown();
+    }
+
+    /// Schedule the flight loop after the startup delay
+    /// as enabling the plugin would
+    pub fn enable(&mut self) {
+        self.next_call = self.tick() + tunables().startup_delay_loops;
+    }
+
+    /// Run `loops` flight loops
+    pub fn run(&mut self, loops: u32) {
+        for _ in 0..loops {
+            self.step();
+        }
+    }
+
+    /// Run for a simulated second,
+    /// long enough for components updating a few times per second
+    pub fn settle(&mut self) {
+        self.run(RETRY_TICKS);
+    }
+
+    /// All writes made by the plugin so far
+    pub fn writes(&self) -> Vec<Write> {
+        self.table.borrow().writes.clone()
+    }
+
+    /// Writes made by the plugin to `name` so far
+    pub fn writes_to(&self, name: &str) -> Vec<Write> {
+        self.table
+            .borrow()
+            .writes
+            .iter()
+            .filter(|write| write.name == name)
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear_writes(&mut self) {
+        self.table.borrow_mut().writes.clear();
+    }
+
+    /// How often the plugin looked up `name`
+    pub fn lookups(&self, name: &str) -> u32 {
+        self.table
+            .borrow()
+            .lookups
+            .get(name)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+struct FakeDataRefs(Rc<RefCell<Table>>);
+
+impl FakeDataRefs {
+    fn find(
+        &self,
+        name: &str,
+        writeable: bool,
+        matches: fn(&Value) -> bool,
+    ) -> Result<Box<FakeDataRef>, PluginError> {
+        *self
+            .0
+            .borrow_mut()
+            .lookups
+            .entry(name.to_string())
+            .or_default() += 1;
+
+        let table = self.0.borrow();
+        let entry = table
+            .entries
+            .get(name)
+            .filter(|entry| {
+                table.tick >= entry.available_at && matches(&entry.value)
+            })
+            .ok_or_else(|| PluginError::DataRefNotFound(name.to_string()))?;
+
+        if writeable && !entry.writeable {
+            return Err(PluginError::DataRefNotWriteable(name.to_string()));
+        }
+
+        Ok(Box::new(FakeDataRef {
+            table: Rc::clone(&self.0),
+            name: name.to_string(),
+        }))
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        writeable: bool,
+        value: Value,
+    ) -> Result<Box<FakeDataRef>, PluginError> {
+        let mut table = self.0.borrow_mut();
+        if table.entries.contains_key(name) {
+            return Err(PluginError::DataRefNotCreated(name.to_string()));
+        }
+        table.entries.insert(
+            name.to_string(),
+            Entry {
+                value,
+                writeable,
+                available_at: 0,
+            },
+        );
+
+        Ok(Box::new(FakeDataRef {
+            table: Rc::clone(&self.0),
+            name: name.to_string(),
+        }))
+    }
+}
+
+impl DataRefProvider for FakeDataRefs {
+    fn find_i32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<i32>>, PluginError> {
+        Ok(self.find(name, false, |value| matches!(value, Value::Int(_)))?)
+    }
+
+    fn find_i32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        Ok(self.find(name, true, |value| matches!(value, Value::Int(_)))?)
+    }
+
+    fn find_f32(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarRead<f32>>, PluginError> {
+        Ok(self.find(name, false, |value| matches!(value, Value::Float(_)))?)
+    }
+
+    fn find_f32_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        Ok(self.find(name, true, |value| matches!(value, Value::Float(_)))?)
+    }
+
+    fn find_f32_array(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayRead<f32>>, PluginError> {
+        Ok(self.find(name, false, |value| {
+            matches!(value, Value::FloatArray(_))
+        })?)
+    }
+
+    fn find_f32_array_writeable(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn ArrayReadWrite<f32>>, PluginError> {
+        Ok(self
+            .find(name, true, |value| matches!(value, Value::FloatArray(_)))?)
+    }
+
+    fn find_string(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn StringRead>, PluginError> {
+        Ok(self.find(name, false, |value| matches!(value, Value::String(_)))?)
+    }
+
+    fn create_i32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<i32>>, PluginError> {
+        Ok(self.create(name, writeable, Value::Int(0))?)
+    }
+
+    fn create_f32(
+        &self,
+        name: &str,
+        writeable: bool,
+    ) -> Result<Box<dyn ScalarReadWrite<f32>>, PluginError> {
+        Ok(self.create(name, writeable, Value::Float(0.0))?)
+    }
+}
+
+struct FakeDataRef {
+    table: Rc<RefCell<Table>>,
+    name: String,
+}
+
+impl ScalarRead<i32> for FakeDataRef {
+    fn get(&self) -> i32 {
+        match self.table.borrow().get(&self.name) {
+            Value::Int(value) => *value,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ScalarReadWrite<i32> for FakeDataRef {
+    fn set(&mut self, value: i32) {
+        self.table.borrow_mut().set(&self.name, Value::Int(value));
+    }
+}
+
+impl ScalarRead<f32> for FakeDataRef {
+    fn get(&self) -> f32 {
+        match self.table.borrow().get(&self.name) {
+            Value::Float(value) => *value,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ScalarReadWrite<f32> for FakeDataRef {
+    fn set(&mut self, value: f32) {
+        self.table.borrow_mut().set(&self.name, Value::Float(value));
+    }
+}
+
+impl ArrayRead<f32> for FakeDataRef {
+    fn get(&self, dest: &mut [f32]) -> usize {
+        match self.table.borrow().get(&self.name) {
+            Value::FloatArray(values) => {
+                let count = dest.len().min(values.len());
+                dest[..count].copy_from_slice(&values[..count]);
+                count
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ArrayReadWrite<f32> for FakeDataRef {
+    fn set(&mut self, values: &[f32]) {
+        let mut table = self.table.borrow_mut();
+        let Value::FloatArray(current) = table.get(&self.name) else {
+            unreachable!()
+        };
+
+        // Like XPLMSetDatavf, only the given values are replaced
+        let mut new = current.clone();
+        let count = new.len().min(values.len());
+        new[..count].copy_from_slice(&values[..count]);
+        table.set(&self.name, Value::FloatArray(new));
+    }
+}
+
+impl StringRead for FakeDataRef {
+    fn get_as_string(&self) -> Result<String, PluginError> {
+        match self.table.borrow().get(&self.name) {
+            Value::String(value) => Ok(value.clone()),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,916 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::aircraft::{AircraftVersion, Compatibility, Variant};
+use crate::binding::{Bind, ReadI32, WriteI32, resolve_all};
+use crate::component::{ComponentInfo, PluginComponent, UpdateRate};
+use crate::event::{Event, EventKind};
+use crate::handler::INIT_RETRY_INTERVAL;
+use crate::messaging::{
+    Request, STATE_ACTIVE, STATE_ENABLED, STATE_READY, STATE_SUSPENDED,
+    STATE_UNKNOWN,
+};
+use crate::recorder::{Format, MAGIC};
+use crate::registry::InitState;
+use crate::replay::{self, Outcome};
+use crate::scenario;
+use crate::settings::{SETTINGS, Settings, SettingsScope, Tunables, tunables};
+use crate::sim::{
+    AIRCRAFT, FRAME_TIME, FakeSim, Notification, RETRY_TICKS, SASL_DELAY,
+    Value, lock_settings,
+};
+
+const BUS_VOLTS: &str = "sim/cockpit2/electrical/bus_volts";
+const GPU_AVAILABLE: &str = "thranda/electrical/ExtPwrGPUAvailable";
+const GPU_VOLTS: &str = "sim/cockpit2/electrical/GPU_generator_volts";
+const OVERRIDE_GPU_VOLTS: &str = "sim/operation/override/override_GPU_volts";
+const HYDRAULIC_PRESSURE_GREEN: &str =
+    "sim/cockpit2/hydraulics/indicators/hydraulic_pressure_2";
+const OVERRIDE_WHEEL_STEER: &str =
+    "sim/operation/override/override_wheel_steer";
+const GPS1_POWER: &str = "sim/cockpit2/radios/actuators/gps_power";
+const GPS2_POWER: &str = "sim/cockpit2/radios/actuators/gps2_power";
+const COM1_POWER: &str = "sim/cockpit2/radios/actuators/com1_power";
+const COM2_POWER: &str = "sim/cockpit2/radios/actuators/com2_power";
+const THRANDA_COM1_POWER: &str = "thranda/generic/com1/genCom1Pwr";
+const THRANDA_COM2_POWER: &str = "thranda/generic/com1/genCom2Pwr";
+const HSI_SELECTOR: &str = "sim/cockpit/switches/HSI_selector";
+const HSI_SELECTOR2: &str = "sim/cockpit/switches/HSI_selector2";
+const HSI_OBS_PILOT: &str =
+    "sim/cockpit2/radios/actuators/hsi_obs_deg_mag_pilot";
+const HSI_OBS_COPILOT: &str =
+    "sim/cockpit2/radios/actuators/hsi_obs_deg_mag_copilot";
+const HSI_DOTS_PILOT: &str = "thranda/anim/hsiHdefDotsPilot";
+const HSI_DOTS_COPILOT: &str = "thranda/anim/hsiHdefDotsCoPilot";
+const THROTTLE_RATIO: &str = "sim/cockpit2/engine/actuators/throttle_ratio";
+const TWEAKS_GPU_ACTIVE: &str = "telephono/tweaks/gpu/active";
+const TWEAKS_GPU_INITIALIZED: &str = "telephono/tweaks/gpu/initialized";
+const TWEAKS_NOSEWHEEL_ENABLED: &str =
+    "telephono/tweaks/nosewheel_steering/enabled";
+const TWEAKS_GPU_VOLTS: &str = "telephono/tweaks/settings/gpu_volts";
+
+/// Simulator with all components initialized
+fn initialized_sim() -> FakeSim {
+    let mut sim = FakeSim::bae146();
+    while !sim.step() {
+        assert!(
+            sim.tick() <= SASL_DELAY + RETRY_TICKS,
+            "components never initialized"
+        );
+    }
+    sim.clear_writes();
+    sim
+}
+
+#[test]
+fn components_wait_for_sasl_datarefs() {
+    let mut sim = FakeSim::bae146();
+
+    for _ in 0..SASL_DELAY {
+        assert!(!sim.step());
+    }
+    assert!(sim.writes_to(OVERRIDE_GPU_VOLTS).is_empty());
+
+    // Next attempt picks them up
+    sim.run(RETRY_TICKS - SASL_DELAY);
+    assert!(sim.step());
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(1));
+}
+
+#[test]
+fn flight_loop_sleeps_until_a_component_is_ready() {
+    let mut sim = FakeSim::empty();
+
+    // Nothing to do until the next initialization attempt
+    sim.run(RETRY_TICKS);
+    assert_eq!(sim.last_call(), Some(0));
+    sim.step();
+    assert_eq!(sim.last_call(), Some(RETRY_TICKS));
+
+    // Ready components run every flight loop
+    sim.define(BUS_VOLTS, [0.0_f32; 6], false);
+    sim.define(GPU_VOLTS, 0.0, true);
+    sim.define(OVERRIDE_GPU_VOLTS, 0, true);
+    sim.define(GPU_AVAILABLE, 0, false);
+    sim.run(RETRY_TICKS);
+    assert_eq!(sim.init_state("gpu"), InitState::Ready);
+    let ready_at = sim.last_call().expect("flight loop ran");
+    sim.step();
+    assert_eq!(sim.last_call(), Some(ready_at + 1));
+}
+
+#[test]
+fn missing_dataref_blocks_initialization() {
+    let mut sim = FakeSim::empty();
+    sim.run(SASL_DELAY * 2);
+
+    assert!(!sim.step());
+    assert!(sim.writes().is_empty());
+}
+
+#[test]
+fn missing_sasl_dataref_times_out() {
+    let mut sim = FakeSim::bae146();
+    sim.remove(HSI_DOTS_PILOT);
+
+    sim.run(RETRY_TICKS + 1);
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(1));
+    let InitState::Pending {
+        reason: Some(reason),
+        ..
+    } = sim.init_state("copilot_hsi")
+    else {
+        panic!("copilot HSI should still be waiting");
+    };
+    assert!(reason.contains(HSI_DOTS_PILOT));
+
+    let timeout = Duration::from_secs_f32(Tunables::DEFAULT.init_timeout_secs);
+    while !sim.step() {
+        assert!(FRAME_TIME * sim.tick() <= timeout + INIT_RETRY_INTERVAL);
+    }
+    assert!(matches!(
+        sim.init_state("copilot_hsi"),
+        InitState::Failed { reason } if reason.contains(HSI_DOTS_PILOT)
+    ));
+
+    // Other components keep running
+    sim.set(GPU_AVAILABLE, 1);
+    sim.settle();
+    assert_eq!(sim.get(GPU_VOLTS), Value::Float(27.5));
+}
+
+#[test]
+fn ready_components_run_while_others_wait() {
+    let _settings = lock_settings();
+    let mut sim = FakeSim::bae146();
+    sim.remove(HSI_DOTS_PILOT);
+    sim.run(RETRY_TICKS + 1);
+
+    // Throttle sync runs every flight loop
+    for ratio in [0.3_f32, 0.4, 0.5] {
+        sim.set(THROTTLE_RATIO, [0.0_f32, ratio, 0.0, 0.0]);
+        sim.step();
+        assert_eq!(
+            sim.get(THROTTLE_RATIO),
+            Value::FloatArray(vec![0.0, ratio, ratio, ratio])
+        );
+    }
+
+    // Copilot HSI only retries once per interval
+    let lookups = sim.lookups(HSI_DOTS_PILOT);
+    sim.run(RETRY_TICKS * 3);
+    assert_eq!(sim.lookups(HSI_DOTS_PILOT), lookups + 3);
+}
+
+#[test]
+fn gpu_volts_follow_gpu_availability() {
+    let mut sim = initialized_sim();
+
+    sim.set(GPU_AVAILABLE, 1);
+    sim.settle();
+    assert_eq!(sim.get(GPU_VOLTS), Value::Float(27.5));
+
+    sim.set(GPU_AVAILABLE, 0);
+    sim.settle();
+    assert_eq!(sim.get(GPU_VOLTS), Value::Float(0.0));
+}
+
+#[test]
+fn radio_power_follows_bus_volts() {
+    let mut sim = initialized_sim();
+    sim.set(GPS1_POWER, 1);
+    sim.set(THRANDA_COM1_POWER, 1);
+
+    sim.set(BUS_VOLTS, [28.0_f32, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    sim.settle();
+    assert_eq!(sim.get(COM1_POWER), Value::Int(1));
+    assert_eq!(sim.get(COM2_POWER), Value::Int(0));
+
+    // 21 V is not enough to power the radio
+    sim.set(BUS_VOLTS, [21.0_f32, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    sim.settle();
+    assert_eq!(sim.get(COM1_POWER), Value::Int(0));
+
+    // Only a powered radio is switched off
+    sim.clear_writes();
+    sim.set(COM2_POWER, 2);
+    sim.settle();
+    assert!(sim.writes_to(COM2_POWER).is_empty());
+}
+
+#[test]
+fn radio_power_follows_the_buses_of_the_variant() {
+    let mut sim = FakeSim::bae146();
+    sim.set("sim/aircraft/view/acf_ICAO", "B463");
+    sim.detect_aircraft(&[]).unwrap();
+    sim.run(SASL_DELAY);
+    sim.settle();
+    sim.set(GPS2_POWER, 1);
+    sim.set(THRANDA_COM2_POWER, 1);
+
+    // The -300 powers COM2 from the third bus
+    sim.set(BUS_VOLTS, [0.0_f32, 28.0, 0.0, 0.0, 0.0, 0.0]);
+    sim.settle();
+    assert_eq!(sim.get(COM2_POWER), Value::Int(0));
+    sim.set(BUS_VOLTS, [0.0_f32, 0.0, 28.0, 0.0, 0.0, 0.0]);
+    sim.settle();
+    assert_eq!(sim.get(COM2_POWER), Value::Int(1));
+}
+
+#[test]
+fn nosewheel_steering_follows_green_pressure() {
+    let mut sim = initialized_sim();
+
+    sim.set(HYDRAULIC_PRESSURE_GREEN, 150.0);
+    sim.step();
+    assert_eq!(sim.get(OVERRIDE_WHEEL_STEER), Value::Int(1));
+
+    // 100 psi is not enough to steer
+    sim.set(HYDRAULIC_PRESSURE_GREEN, 100.0);
+    sim.step();
+    assert_eq!(sim.get(OVERRIDE_WHEEL_STEER), Value::Int(0));
+}
+
+#[test]
+fn components_update_at_their_rate() {
+    let mut sim = initialized_sim();
+    sim.set(GPU_AVAILABLE, 1);
+
+    // Connecting the GPU is handled right away
+    sim.step();
+    assert_eq!(sim.get(GPU_VOLTS), Value::Float(27.5));
+
+    // GPU volts are checked four times per second
+    sim.clear_writes();
+    for _ in 0..RETRY_TICKS {
+        sim.set(GPU_VOLTS, 0.0);
+        sim.step();
+    }
+    assert_eq!(sim.writes_to(GPU_VOLTS).len(), 4);
+
+    // Nosewheel steering only runs when its inputs change
+    sim.clear_writes();
+    sim.run(RETRY_TICKS);
+    assert!(sim.writes_to(OVERRIDE_WHEEL_STEER).is_empty());
+
+    sim.set(OVERRIDE_WHEEL_STEER, 1);
+    sim.step();
+    assert_eq!(sim.get(OVERRIDE_WHEEL_STEER), Value::Int(0));
+}
+
+#[test]
+fn disabled_component_is_skipped() {
+    let mut sim = initialized_sim();
+    sim.set_enabled("nosewheel_steering", false);
+    sim.clear_writes();
+
+    sim.set(HYDRAULIC_PRESSURE_GREEN, 150.0);
+    sim.step();
+    assert!(sim.writes_to(OVERRIDE_WHEEL_STEER).is_empty());
+
+    sim.set_enabled("nosewheel_steering", true);
+    sim.run(RETRY_TICKS);
+    assert_eq!(sim.get(OVERRIDE_WHEEL_STEER), Value::Int(1));
+}
+
+#[test]
+fn overrides_are_restored_on_teardown() {
+    let _settings = lock_settings();
+    let mut sim = FakeSim::bae146();
+    sim.set(OVERRIDE_WHEEL_STEER, 0);
+    sim.set(HYDRAULIC_PRESSURE_GREEN, 150.0);
+    sim.run(RETRY_TICKS + 1);
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(1));
+    assert_eq!(sim.get(OVERRIDE_WHEEL_STEER), Value::Int(1));
+
+    sim.set_enabled("nosewheel_steering", false);
+    assert_eq!(sim.get(OVERRIDE_WHEEL_STEER), Value::Int(0));
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(1));
+
+    sim.teardown();
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(0));
+    sim.run(RETRY_TICKS);
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(0));
+
+    // Components take over again after the startup delay
+    // once the plugin is enabled again
+    sim.enable();
+    sim.run(tunables().startup_delay_loops);
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(0));
+    sim.step();
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(1));
+}
+
+#[test]
+fn copilot_hsi_follows_pilot_in_rnav_mode() {
+    let mut sim = initialized_sim();
+    sim.set(HSI_OBS_PILOT, 120.0);
+    sim.set(HSI_DOTS_PILOT, 1.5);
+
+    sim.set(HSI_SELECTOR, 2);
+    sim.step();
+    assert!(sim.writes_to(HSI_OBS_COPILOT).is_empty());
+
+    sim.set_at(sim.tick() + 5, HSI_SELECTOR2, 2);
+    sim.run(5);
+    assert!(sim.writes_to(HSI_OBS_COPILOT).is_empty());
+
+    sim.step();
+    assert_eq!(sim.get(HSI_OBS_COPILOT), Value::Float(120.0));
+    assert_eq!(sim.get(HSI_DOTS_COPILOT), Value::Float(1.5));
+}
+
+#[test]
+fn copilot_hsi_syncs_on_command() {
+    let mut sim = FakeSim::bae146();
+    sim.set(HSI_OBS_PILOT, 120.0);
+    assert!(!sim.run_action("copilot_hsi", "sync_now"));
+
+    sim.run(RETRY_TICKS + 1);
+    sim.set(HSI_SELECTOR, 0);
+    sim.set(HSI_SELECTOR2, 2);
+    sim.step();
+    assert_eq!(sim.get(HSI_OBS_COPILOT), Value::Float(0.0));
+
+    assert!(sim.run_action("copilot_hsi", "sync_now"));
+    assert_eq!(sim.get(HSI_OBS_COPILOT), Value::Float(120.0));
+}
+
+#[test]
+fn throttle_levers_follow_lever_two() {
+    let _settings = lock_settings();
+    let mut sim = initialized_sim();
+
+    sim.set(THROTTLE_RATIO, [0.2_f32, 0.7, 0.1, 0.0]);
+    sim.step();
+    assert_eq!(
+        sim.get(THROTTLE_RATIO),
+        Value::FloatArray(vec![0.2, 0.7, 0.7, 0.7])
+    );
+}
+
+#[test]
+fn published_datarefs_follow_component_state() {
+    let mut sim = FakeSim::bae146();
+    assert_eq!(sim.get(TWEAKS_GPU_INITIALIZED), Value::Int(0));
+
+    sim.run(RETRY_TICKS + 1);
+    assert_eq!(sim.get(TWEAKS_GPU_INITIALIZED), Value::Int(1));
+    assert_eq!(sim.get(TWEAKS_GPU_ACTIVE), Value::Int(0));
+
+    sim.set(GPU_AVAILABLE, 1);
+    sim.step();
+    assert_eq!(sim.get(TWEAKS_GPU_ACTIVE), Value::Int(1));
+}
+
+#[test]
+fn published_datarefs_change_behaviour() {
+    let _settings = lock_settings();
+    let mut sim = initialized_sim();
+    sim.set(HYDRAULIC_PRESSURE_GREEN, 150.0);
+    sim.step();
+    assert_eq!(sim.get(OVERRIDE_WHEEL_STEER), Value::Int(1));
+
+    // Another plugin switches nosewheel steering off
+    sim.set(TWEAKS_NOSEWHEEL_ENABLED, 0);
+    sim.step();
+    assert_eq!(sim.get(OVERRIDE_WHEEL_STEER), Value::Int(0));
+    assert_eq!(sim.get(TWEAKS_NOSEWHEEL_ENABLED), Value::Int(0));
+    assert_eq!(sim.notifications().len(), 1);
+
+    // Writing the same state again changes nothing, nobody is told
+    sim.set(TWEAKS_NOSEWHEEL_ENABLED, 0);
+    sim.step();
+    assert_eq!(sim.notifications().len(), 1);
+    // The choice in the settings stays the user's
+    assert_eq!(*SETTINGS.lock().unwrap(), Settings::new());
+
+    // Out of range settings are rejected
+    sim.set(TWEAKS_GPU_VOLTS, 400.0);
+    sim.step();
+    assert_eq!(sim.get(TWEAKS_GPU_VOLTS), Value::Float(27.5));
+
+    // Writes wait while the settings are busy, e.g. in the menu
+    let busy = SETTINGS.lock().unwrap();
+    sim.set(TWEAKS_GPU_VOLTS, 30.0);
+    sim.step();
+    assert_eq!(sim.get(TWEAKS_GPU_VOLTS), Value::Float(30.0));
+    drop(busy);
+    // Applied, not reverted to the old setting
+    sim.step();
+    assert_eq!(sim.get(TWEAKS_GPU_VOLTS), Value::Float(30.0));
+    SETTINGS.lock().unwrap().tunables = Tunables::DEFAULT;
+}
+
+#[test]
+fn fixes_switch_off_once_fixed_upstream() {
+    let table = [Compatibility {
+        id: "gpu",
+        fixed_in: Some(AircraftVersion::new(1, 1, 0)),
+    }];
+
+    let mut sim = FakeSim::bae146();
+    sim.define("sim/aircraft/view/acf_descrip", "BAe 146-200 v1.0.2", false);
+    assert_eq!(
+        sim.detect_aircraft(&table).unwrap().version,
+        Some(AircraftVersion::new(1, 0, 2))
+    );
+    sim.run(SASL_DELAY);
+    sim.settle();
+    assert_eq!(sim.get(OVERRIDE_GPU_VOLTS), Value::Int(1));
+
+    let mut sim = FakeSim::bae146();
+    sim.define("sim/aircraft/view/acf_notes", "Version 1.1.0", false);
+    sim.detect_aircraft(&table).unwrap();
+    sim.run(SASL_DELAY);
+    sim.settle();
+    assert!(sim.writes_to(OVERRIDE_GPU_VOLTS).is_empty());
+    assert_eq!(sim.get("telephono/tweaks/gpu/enabled"), Value::Int(0));
+
+    // Without a version every fix keeps running
+    let mut sim = FakeSim::bae146();
+    assert_eq!(sim.detect_aircraft(&table).unwrap().version, None);
+}
+
+#[test]
+fn aircraft_variant_follows_icao_code() {
+    let mut sim = FakeSim::bae146();
+    assert_eq!(sim.detect_aircraft(&[]).unwrap(), AIRCRAFT);
+
+    sim.set("sim/aircraft/view/acf_ICAO", "B463");
+    assert_eq!(sim.detect_aircraft(&[]).unwrap().variant, Variant::B463);
+
+    sim.set("sim/aircraft/view/acf_ICAO", "B744");
+    assert!(sim.detect_aircraft(&[]).is_err());
+}
+
+#[test]
+fn bindings_resolve_once_available() {
+    let mut sim = FakeSim::empty();
+    let data = sim.data();
+    let mut required = WriteI32::required("test/required");
+    let mut other = ReadI32::required("test/other");
+
+    assert!(
+        resolve_all(data.as_ref(), |visit| {
+            visit(&mut required);
+            visit(&mut other);
+        })
+        .is_err()
+    );
+    // Unbound datarefs read as default and ignore writes
+    assert_eq!(required.get(), 0);
+    required.set(1);
+
+    // Resolving carries on past missing datarefs
+    sim.define("test/required", 2, true);
+    assert!(
+        resolve_all(data.as_ref(), |visit| {
+            visit(&mut other);
+            visit(&mut required);
+        })
+        .is_err()
+    );
+    assert!(required.is_bound());
+    assert!(!other.is_bound());
+    assert_eq!(required.get(), 2);
+    required.set(3);
+    assert_eq!(sim.get("test/required"), Value::Int(3));
+
+    sim.define("test/other", 4, false);
+    resolve_all(data.as_ref(), |visit| {
+        visit(&mut required);
+        visit(&mut other);
+    })
+    .unwrap();
+    assert_eq!(other.get(), 4);
+
+    // Bound datarefs are not looked up again
+    assert_eq!(sim.lookups("test/required"), 2);
+}
+
+/// Component which only records the events it receives
+struct EventRecorder(Rc<RefCell<Vec<Event>>>);
+
+impl PluginComponent for EventRecorder {
+    fn info(&self) -> ComponentInfo {
+        ComponentInfo {
+            id: "recorder",
+            name: "Event recorder",
+            description: "Records events for tests",
+            enabled_by_default: true,
+        }
+    }
+
+    fn bindings(&mut self, _visit: &mut dyn FnMut(&mut dyn Bind)) {}
+
+    fn update(&mut self) {}
+
+    fn events(&self) -> &'static [EventKind] {
+        &[EventKind::GpuConnected, EventKind::ComponentEnabled]
+    }
+
+    fn on_event(&mut self, event: &Event) {
+        self.0.borrow_mut().push(event.clone());
+    }
+}
+
+/// Component which counts its updates
+struct UpdateCounter {
+    id: &'static str,
+    rate: UpdateRate,
+    updates: Rc<Cell<u32>>,
+}
+
+impl PluginComponent for UpdateCounter {
+    fn info(&self) -> ComponentInfo {
+        ComponentInfo {
+            id: self.id,
+            name: "Update counter",
+            description: "Counts updates for tests",
+            enabled_by_default: true,
+        }
+    }
+
+    fn update_rate(&self) -> UpdateRate {
+        self.rate
+    }
+
+    fn bindings(&mut self, _visit: &mut dyn FnMut(&mut dyn Bind)) {}
+
+    fn update(&mut self) {
+        self.updates.set(self.updates.get() + 1);
+    }
+}
+
+#[test]
+fn invalid_update_rates_fall_back_to_every_frame() {
+    let mut sim = initialized_sim();
+    let rates = [("zero", 0.0), ("negative", -4.0), ("nan", f32::NAN)];
+    let counters = rates.map(|(id, hz)| {
+        let updates = Rc::new(Cell::new(0));
+        sim.register(UpdateCounter {
+            id,
+            rate: UpdateRate::Hz(hz),
+            updates: Rc::clone(&updates),
+        });
+        updates
+    });
+    sim.settle();
+
+    let before = counters.each_ref().map(|updates| updates.get());
+    sim.run(5);
+    for (updates, before) in counters.iter().zip(before) {
+        assert_eq!(updates.get(), before + 5);
+    }
+}
+
+#[test]
+fn components_receive_transitions_as_events() {
+    let mut sim = initialized_sim();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    sim.register(EventRecorder(Rc::clone(&events)));
+    sim.settle();
+
+    // Nothing changed so far
+    assert!(events.borrow().is_empty());
+
+    sim.set(GPU_AVAILABLE, 1);
+    sim.step();
+    sim.step();
+    sim.set_enabled("radio", false);
+    sim.step();
+
+    assert_eq!(
+        *events.borrow(),
+        [
+            Event::GpuConnected(true),
+            Event::ComponentEnabled {
+                id: "radio",
+                enabled: false
+            },
+        ]
+    );
+}
+
+#[test]
+fn events_watch_the_datarefs_components_bind() {
+    let mut sim = FakeSim::bae146();
+    sim.remove(GPU_AVAILABLE);
+    sim.run(RETRY_TICKS * 3);
+
+    // Only the GPU fix looks it up, once per interval
+    assert_eq!(sim.lookups(GPU_AVAILABLE), 3);
+}
+
+#[test]
+fn diagnostics_show_state_and_dataref_values() {
+    let mut sim = FakeSim::bae146();
+    sim.remove(GPU_AVAILABLE);
+    sim.run(SASL_DELAY);
+    sim.settle();
+    sim.set(HYDRAULIC_PRESSURE_GREEN, 150.0);
+    sim.step();
+
+    let report = sim.report();
+    let line = |start: &str| {
+        report
+            .iter()
+            .find(|line| line.trim_start().starts_with(start))
+            .unwrap_or_else(|| panic!("no line for {start} in {report:#?}"))
+            .clone()
+    };
+
+    assert!(line("Fix GPU external power").contains("waiting"));
+    assert_eq!(
+        line(GPU_AVAILABLE).trim(),
+        format!("{GPU_AVAILABLE} = missing")
+    );
+    assert!(line("Fix nosewheel steering").contains("ready, active"));
+    assert!(line("Fix nosewheel steering").contains("last active 0.0s ago"));
+    assert!(line("Fix copilot HSI").ends_with("ready, never active"));
+    assert_eq!(
+        line(HYDRAULIC_PRESSURE_GREEN).trim(),
+        format!("{HYDRAULIC_PRESSURE_GREEN} = 150")
+    );
+    assert_eq!(
+        line(BUS_VOLTS).trim(),
+        format!("{BUS_VOLTS} = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0]")
+    );
+}
+
+#[test]
+fn other_plugins_query_and_switch_components() {
+    let mut sim = initialized_sim();
+
+    assert_eq!(
+        sim.message(Request::QueryState, "gpu"),
+        STATE_ENABLED | STATE_READY
+    );
+    assert_eq!(sim.message(Request::QueryState, "apu"), STATE_UNKNOWN);
+
+    assert_eq!(sim.message(Request::Disable, "nosewheel_steering"), 0);
+    sim.step();
+    assert_eq!(
+        sim.notifications(),
+        [Notification {
+            id: "nosewheel_steering".to_string(),
+            state: 0,
+            suspended: Duration::ZERO,
+        }]
+    );
+    assert_eq!(sim.get(TWEAKS_NOSEWHEEL_ENABLED), Value::Int(0));
+
+    // Nothing changes, nobody is told
+    sim.message(Request::Disable, "nosewheel_steering");
+    sim.step();
+    assert_eq!(sim.notifications().len(), 1);
+}
+
+#[test]
+fn other_plugins_suspend_throttle_sync() {
+    let _settings = lock_settings();
+    let mut sim = initialized_sim();
+    let ready = STATE_ENABLED | STATE_READY | STATE_ACTIVE;
+
+    let state = sim
+        .message(Request::Suspend(Duration::from_secs(1)), "throttle_levers");
+    assert_eq!(state, ready | STATE_SUSPENDED);
+
+    // An external autothrottle drives the levers
+    sim.set(THROTTLE_RATIO, [0.2_f32, 0.7, 0.1, 0.0]);
+    sim.step();
+    assert_eq!(
+        sim.get(THROTTLE_RATIO),
+        Value::FloatArray(vec![0.2, 0.7, 0.1, 0.0])
+    );
+    assert!(sim.report().iter().any(|line| {
+        line.starts_with("Sync throttle levers: ready, suspended")
+    }));
+
+    sim.settle();
+    sim.step();
+    assert_eq!(
+        sim.get(THROTTLE_RATIO),
+        Value::FloatArray(vec![0.2, 0.7, 0.7, 0.7])
+    );
+    let states: Vec<_> = sim
+        .notifications()
+        .into_iter()
+        .map(|notification| (notification.id, notification.state))
+        .collect();
+    assert_eq!(
+        states,
+        [
+            ("throttle_levers".to_string(), ready | STATE_SUSPENDED),
+            ("throttle_levers".to_string(), ready),
+        ]
+    );
+}
+
+#[test]
+fn recording_captures_reads_and_writes() {
+    let mut sim = initialized_sim();
+    let csv = sim.start_recording(Format::Csv);
+    let binary = {
+        let mut binary_sim = initialized_sim();
+        let binary = binary_sim.start_recording(Format::Binary);
+        binary_sim.set(GPU_AVAILABLE, 1);
+        binary_sim.step();
+        binary_sim.stop_recording();
+        binary.contents()
+    };
+
+    sim.set(GPU_AVAILABLE, 1);
+    sim.step();
+    sim.step();
+    sim.stop_recording();
+    sim.set(GPU_AVAILABLE, 0);
+    sim.step();
+
+    let csv = String::from_utf8(csv.contents()).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert!(lines[0].starts_with("# BAe 146 Tweaks v"));
+    assert!(lines.contains(&"# aircraft = B462"));
+    assert!(lines.contains(&"# gpu_volts = 27.5"));
+    assert!(lines.contains(&"time,kind,type,dataref,value"));
+
+    let records: Vec<&str> = lines
+        .iter()
+        .copied()
+        .filter(|line| line.starts_with("0."))
+        .collect();
+    // Changes only, in the order the plugin saw and made them
+    let position = |record: &str| {
+        records
+            .iter()
+            .position(|line| *line == record)
+            .unwrap_or_else(|| panic!("no {record} in {records:#?}"))
+    };
+    assert!(
+        position(&format!("0.000,read,int,{GPU_AVAILABLE},1"))
+            < position(&format!("0.000,write,float,{GPU_VOLTS},27.5"))
+    );
+    assert_eq!(
+        records
+            .iter()
+            .filter(|line| line.contains(BUS_VOLTS))
+            .collect::<Vec<_>>(),
+        [&format!("0.000,read,floats,{BUS_VOLTS},0 0 0 0 0 0")]
+    );
+    assert!(!csv.contains(&format!("write,float,{GPU_VOLTS},0\n")));
+
+    assert!(binary.starts_with(MAGIC));
+    assert!(
+        binary
+            .windows(GPU_VOLTS.len())
+            .any(|w| w == GPU_VOLTS.as_bytes())
+    );
+    assert!(binary.len() < csv.len());
+}
+
+#[test]
+fn replayed_recording_matches_the_current_fixes() {
+    let _settings = lock_settings();
+    let record = |format| {
+        let mut sim = initialized_sim();
+        let buffer = sim.start_recording(format);
+        sim.set(GPU_AVAILABLE, 1);
+        sim.run(3);
+        sim.set(GPU_AVAILABLE, 0);
+        sim.run(3);
+        sim.stop_recording();
+        buffer.contents()
+    };
+
+    for format in [Format::Csv, Format::Binary] {
+        let report = replay::run(&record(format)).unwrap();
+        assert_eq!(report.differences(), 0, "{format}:\n{report}");
+        assert!(
+            report
+                .writes
+                .iter()
+                .any(|(_, write)| write.name == GPU_VOLTS)
+        );
+    }
+
+    // A recording made by a plugin writing other values
+    let csv = String::from_utf8(record(Format::Csv)).unwrap();
+    let changed = csv.replace(
+        &format!("write,float,{GPU_VOLTS},27.5"),
+        &format!("write,float,{GPU_VOLTS},28"),
+    );
+    assert_ne!(changed, csv);
+    let report = replay::run(changed.as_bytes()).unwrap();
+    let gpu_volts: Vec<_> = report
+        .writes
+        .iter()
+        .filter(|(_, write)| write.name == GPU_VOLTS)
+        .map(|(outcome, write)| (*outcome, write.value.to_string()))
+        .collect();
+    assert!(gpu_volts.contains(&(Outcome::Added, "27.5".to_string())));
+    assert!(gpu_volts.contains(&(Outcome::Missing, "28".to_string())));
+
+    // The recorded settings don't outlive the replay
+    SETTINGS.lock().unwrap().log_file = true;
+    replay::run(csv.as_bytes()).unwrap();
+    assert!(SETTINGS.lock().unwrap().log_file);
+    SETTINGS.lock().unwrap().log_file = false;
+}
+
+#[test]
+fn swapped_settings_are_restored_after_a_panic() {
+    let _settings = lock_settings();
+    let mut swapped = Settings::new();
+    swapped.log_file = true;
+
+    let result = std::panic::catch_unwind(|| {
+        let _scope = SettingsScope::enter(swapped);
+        assert!(SETTINGS.lock().unwrap().log_file);
+        panic!("replay failed");
+    });
+    assert!(result.is_err());
+    assert_eq!(*SETTINGS.lock().unwrap(), Settings::new());
+}
+
+#[test]
+fn golden_scenarios_pass() {
+    let failures: Vec<String> = scenario::SCENARIOS
+        .iter()
+        .flat_map(|(file, text)| scenario::run(file, text))
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn profiled_updates_show_in_diagnostics_and_profile() {
+    let _settings = lock_settings();
+    let set_profiling = |enabled| {
+        SETTINGS.lock().unwrap().profile_updates = enabled;
+    };
+    let mut sim = initialized_sim();
+    sim.run(3);
+    assert!(sim.profile().contains("\nthrottle_levers,0,"));
+
+    set_profiling(true);
+    sim.set(THROTTLE_RATIO, [0.2_f32, 0.7, 0.1, 0.0]);
+    sim.run(5);
+    set_profiling(false);
+    sim.run(3);
+
+    // Throttle sync reads the levers every update,
+    // but only writes them once levers 3 and 4 are out of step
+    let profile = sim.profile();
+    let lines: Vec<_> = profile.lines().collect();
+    assert_eq!(
+        lines[0],
+        "component,updates,min_us,avg_us,p99_us,max_us,reads,writes"
+    );
+    let throttle_levers = lines
+        .iter()
+        .find(|line| line.starts_with("throttle_levers,"))
+        .unwrap();
+    assert!(throttle_levers.starts_with("throttle_levers,5,"));
+    assert!(throttle_levers.ends_with(",5,1"));
+
+    let report = sim.report();
+    let status = report
+        .iter()
+        .position(|line| line.starts_with("Sync throttle levers: "))
+        .unwrap();
+    let line = &report[status + 1];
+    assert!(line.starts_with("    profile: 5 updates, min "), "{line}");
+    assert!(line.ends_with(", 5 reads, 1 writes"), "{line}");
+}
+
+#[test]
+fn unchanged_values_are_not_written_again() {
+    let _settings = lock_settings();
+    let mut sim = initialized_sim();
+    sim.set(HYDRAULIC_PRESSURE_GREEN, 150.0);
+    sim.set(THROTTLE_RATIO, [0.2_f32, 0.7, 0.1, 0.0]);
+    sim.settle();
+    sim.clear_writes();
+
+    sim.settle();
+    assert!(sim.writes().is_empty(), "{:#?}", sim.writes());
+
+    // Changes by X-Plane or other plugins are still corrected
+    sim.set(THROTTLE_RATIO, [0.2_f32, 0.7, 0.7, 0.1]);
+    sim.step();
+    assert_eq!(sim.writes_to(THROTTLE_RATIO).len(), 1);
+    assert_eq!(
+        sim.get(THROTTLE_RATIO),
+        Value::FloatArray(vec![0.2, 0.7, 0.7, 0.7])
+    );
+
+    // Copilot course within the tolerance of the pilot course
+    sim.set(HSI_SELECTOR, 2);
+    sim.set(HSI_SELECTOR2, 2);
+    sim.set(HSI_OBS_PILOT, 120.0);
+    sim.set(HSI_OBS_COPILOT, 120.0005);
+    sim.clear_writes();
+    sim.step();
+    assert!(sim.writes_to(HSI_OBS_COPILOT).is_empty());
+}
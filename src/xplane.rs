@@ -0,0 +1,368 @@
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::rc::Rc;
+
+use xplm::command::OwnedCommand;
+use xplm::flight_loop::FlightLoop;
+use xplm::menu::{ActionItem, CheckItem, Menu, Separator};
+use xplm::plugin::management::plugin_with_signature;
+use xplm::plugin::{Plugin, PluginInfo};
+
+use crate::aircraft::{Aircraft, COMPATIBILITY};
+use crate::dataref::XPlaneDataRefs;
+use crate::diagnostics;
+use crate::handler::FlightLoopHandler;
+use crate::menu::{
+    ComponentActionCommandHandler, ComponentCommandHandler,
+    ComponentMenuHandler, MenuCheckItems, ProfileExportHandler,
+    RecordingCommandHandler, RecordingMenuHandler, ReloadSettingsHandler,
+    Switch, SyncThrottlesCommandHandler, SyncThrottlesMenuHandler, Toggle,
+    WindowToggleHandler,
+};
+use crate::messaging::{self, XPlaneNotifier};
+use crate::plugin::{PLUGIN_NAME, PLUGIN_PREFIX, PluginError};
+use crate::profile::CountingDataRefs;
+use crate::published::PublishedDataRefs;
+use crate::recorder::{Recorder, RecordingDataRefs};
+use crate::registry::ComponentRegistry;
+use crate::settings::{SETTINGS, Settings, tunables};
+use crate::settings_window::SettingsWindow;
+
+static PLUGIN_SIGNATURE: &str =
+    concat!("io.github.telephono.", env!("CARGO_PKG_NAME"));
+static PLUGIN_DESCRIPTION: &str = "BAe 146 fixes and tweaks";
+
+pub struct TweaksPlugin {
+    components: Rc<RefCell<ComponentRegistry>>,
+    recorder: Rc<RefCell<Recorder>>,
+    flight_loop: FlightLoop,
+    _plugin_menu: Menu,
+    _commands: Vec<OwnedCommand>,
+}
+
+impl Plugin for TweaksPlugin {
+    type Error = PluginError;
+
+    fn start() -> Result<Self, Self::Error> {
+        if plugin_with_signature(PLUGIN_SIGNATURE).is_some() {
+            return Err(PluginError::AlreadyRunning);
+        }
+
+        let plugin_dir = Settings::dir();
+        // <aircraft>/plugins/tweaks
+        let aircraft_dir =
+            plugin_dir.ancestors().nth(2).unwrap_or(&plugin_dir);
+        let aircraft = Aircraft::detect(&XPlaneDataRefs, aircraft_dir)?;
+        info!("starting up for {aircraft}...");
+
+        let settings = Settings::load(&Settings::path());
+        settings.configure_log();
+
+        let recorder = Rc::new(RefCell::new(Recorder::new()));
+        let accesses = Rc::new(Cell::default());
+        let components =
+            Rc::new(RefCell::new(ComponentRegistry::with_components(
+                Rc::new(CountingDataRefs::new(
+                    RecordingDataRefs::new(
+                        XPlaneDataRefs,
+                        Rc::clone(&recorder),
+                    ),
+                    Rc::clone(&accesses),
+                )),
+                aircraft,
+            )));
+        match aircraft.version {
+            _ if COMPATIBILITY.is_empty() => {
+                info!("no fix depends on the aircraft version yet");
+            }
+            Some(version) => components
+                .borrow_mut()
+                .apply_compatibility(COMPATIBILITY, version),
+            None => warn!("unknown aircraft version, keeping all fixes"),
+        }
+        for registered in components.borrow_mut().iter_mut() {
+            registered.enabled = settings.component_enabled(&registered.info);
+        }
+        let sync_throttles = settings.sync_throttles;
+        if let Ok(mut lock) = SETTINGS.lock() {
+            *lock = settings;
+        }
+
+        let published =
+            PublishedDataRefs::new(&XPlaneDataRefs, &components.borrow())?;
+
+        let check_items = Rc::new(MenuCheckItems {
+            sync_throttles: Rc::new(CheckItem::new(
+                "Sync throttles",
+                sync_throttles,
+                SyncThrottlesMenuHandler,
+            )?),
+            components: components
+                .borrow()
+                .iter()
+                .map(|registered| {
+                    let item = CheckItem::new(
+                        registered.info.name,
+                        registered.enabled,
+                        ComponentMenuHandler {
+                            id: registered.info.id,
+                            components: Rc::clone(&components),
+                        },
+                    )?;
+                    Ok((registered.info.id, Rc::new(item)))
+                })
+                .collect::<Result<_, PluginError>>()?,
+        });
+
+        let windows = Windows::new(&components, &check_items);
+        let recording = RecordingControl {
+            item: Rc::new(CheckItem::new(
+                "Record session",
+                false,
+                RecordingMenuHandler {
+                    recorder: Rc::clone(&recorder),
+                    aircraft,
+                },
+            )?),
+            recorder: Rc::clone(&recorder),
+            aircraft,
+        };
+        let plugin_menu =
+            create_menu(&components, &check_items, &windows, &recording)?;
+        let commands =
+            create_commands(&components, &check_items, &windows, &recording)?;
+
+        let handler = FlightLoopHandler::new(Rc::clone(&components))
+            .with_published(published)
+            .with_check_items(check_items)
+            .with_notifier(XPlaneNotifier)
+            .with_recorder(Rc::clone(&recorder))
+            .with_profiling(accesses);
+
+        let plugin = Self {
+            components,
+            recorder,
+            flight_loop: FlightLoop::new(handler),
+            _plugin_menu: plugin_menu,
+            _commands: commands,
+        };
+
+        info!("startup complete");
+        Ok(plugin)
+    }
+
+    fn enable(&mut self) -> Result<(), Self::Error> {
+        self.flight_loop
+            .schedule_after_loops(tunables().startup_delay_loops);
+        info!("enabled");
+        Ok(())
+    }
+
+    fn disable(&mut self) {
+        self.flight_loop.deactivate();
+        self.components.borrow_mut().teardown();
+        self.recorder.borrow_mut().stop();
+        info!("disabled");
+    }
+
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            name: PLUGIN_NAME.to_string(),
+            signature: PLUGIN_SIGNATURE.to_string(),
+            description: PLUGIN_DESCRIPTION.to_string(),
+        }
+    }
+
+    fn receive_message(
+        &mut self,
+        from: i32,
+        message: i32,
+        param: *mut c_void,
+    ) {
+        trace!("message {message:#x} from plugin {from}");
+        let Ok(mut components) = self.components.try_borrow_mut() else {
+            warn!("components busy, message {message:#x} dropped");
+            // SAFETY: other plugins send the documented `ComponentMessage`
+            // with the message ids of this plugin
+            unsafe { messaging::reject(message, param) };
+            return;
+        };
+        // SAFETY: other plugins send the documented `ComponentMessage`
+        // with the message ids of this plugin
+        unsafe { messaging::receive(&mut components, message, param) };
+    }
+}
+
+/// Windows the plugin menu and commands open
+struct Windows {
+    settings: Rc<dyn Toggle>,
+    diagnostics: Rc<dyn Toggle>,
+}
+
+impl Windows {
+    fn new(
+        components: &Rc<RefCell<ComponentRegistry>>,
+        check_items: &Rc<MenuCheckItems>,
+    ) -> Self {
+        Self {
+            settings: Rc::new(SettingsWindow::new(
+                Rc::clone(components),
+                Rc::clone(check_items),
+            )),
+            diagnostics: Rc::new(diagnostics::create_window(Rc::clone(
+                components,
+            ))),
+        }
+    }
+}
+
+/// Session recording, shared by the menu item and the commands
+struct RecordingControl {
+    recorder: Rc<RefCell<Recorder>>,
+    aircraft: Aircraft,
+    item: Rc<CheckItem>,
+}
+
+/// Plugin menu with an item per setting and component
+fn create_menu(
+    components: &Rc<RefCell<ComponentRegistry>>,
+    check_items: &Rc<MenuCheckItems>,
+    windows: &Windows,
+    recording: &RecordingControl,
+) -> Result<Menu, PluginError> {
+    let plugin_menu = Menu::new("BAe 146 Tweaks")?;
+    plugin_menu.add_child(Rc::clone(&check_items.sync_throttles));
+    plugin_menu.add_child(Separator);
+    for (_, item) in &check_items.components {
+        plugin_menu.add_child(Rc::clone(item));
+    }
+    plugin_menu.add_child(Separator);
+    plugin_menu.add_child(ActionItem::new(
+        "Reload settings",
+        ReloadSettingsHandler {
+            components: Rc::clone(components),
+            check_items: Rc::clone(check_items),
+        },
+    )?);
+    plugin_menu.add_child(ActionItem::new(
+        "Settings",
+        WindowToggleHandler {
+            window: Rc::clone(&windows.settings),
+        },
+    )?);
+    plugin_menu.add_child(ActionItem::new(
+        "Diagnostics",
+        WindowToggleHandler {
+            window: Rc::clone(&windows.diagnostics),
+        },
+    )?);
+    plugin_menu.add_child(Rc::clone(&recording.item));
+    plugin_menu.add_to_plugins_menu();
+    Ok(plugin_menu)
+}
+
+/// Commands for everything the plugin menu can do,
+/// plus the one-shot actions of the components
+fn create_commands(
+    components: &Rc<RefCell<ComponentRegistry>>,
+    check_items: &Rc<MenuCheckItems>,
+    windows: &Windows,
+    recording: &RecordingControl,
+) -> Result<Vec<OwnedCommand>, PluginError> {
+    let mut commands = Vec::new();
+
+    for (switch, suffix) in Switch::ALL {
+        commands.push(OwnedCommand::new(
+            &format!("{PLUGIN_PREFIX}/sync_throttles/{suffix}"),
+            &format!("Sync throttles {suffix}"),
+            SyncThrottlesCommandHandler {
+                switch,
+                components: Rc::clone(components),
+                check_items: Rc::clone(check_items),
+            },
+        )?);
+    }
+
+    for registered in components.borrow().iter() {
+        let info = registered.info;
+        for (switch, suffix) in Switch::ALL {
+            commands.push(OwnedCommand::new(
+                &format!("{PLUGIN_PREFIX}/{}/{suffix}", info.id),
+                &format!("{} {suffix}", info.name),
+                ComponentCommandHandler {
+                    id: info.id,
+                    switch,
+                    components: Rc::clone(components),
+                    check_items: Rc::clone(check_items),
+                },
+            )?);
+        }
+
+        for action in registered.component.actions() {
+            commands.push(OwnedCommand::new(
+                &format!("{PLUGIN_PREFIX}/{}/{}", info.id, action.name),
+                action.description,
+                ComponentActionCommandHandler {
+                    id: info.id,
+                    action: action.name,
+                    components: Rc::clone(components),
+                },
+            )?);
+        }
+    }
+
+    commands.push(OwnedCommand::new(
+        &format!("{PLUGIN_PREFIX}/reload_settings"),
+        "Reload settings",
+        ReloadSettingsHandler {
+            components: Rc::clone(components),
+            check_items: Rc::clone(check_items),
+        },
+    )?);
+
+    commands.push(OwnedCommand::new(
+        &format!("{PLUGIN_PREFIX}/settings_window/toggle"),
+        "Show or hide the settings window",
+        WindowToggleHandler {
+            window: Rc::clone(&windows.settings),
+        },
+    )?);
+
+    commands.push(OwnedCommand::new(
+        &format!("{PLUGIN_PREFIX}/diagnostics/toggle"),
+        "Show or hide the diagnostics window",
+        WindowToggleHandler {
+            window: Rc::clone(&windows.diagnostics),
+        },
+    )?);
+
+    commands.push(OwnedCommand::new(
+        &format!("{PLUGIN_PREFIX}/profile/export"),
+        "Write the update profile of the fixes to the plugin folder",
+        ProfileExportHandler {
+            components: Rc::clone(components),
+        },
+    )?);
+
+    for (switch, suffix) in Switch::ALL {
+        commands.push(OwnedCommand::new(
+            &format!("{PLUGIN_PREFIX}/recording/{suffix}"),
+            &format!("Record session {suffix}"),
+            RecordingCommandHandler {
+                switch,
+                recorder: Rc::clone(&recording.recorder),
+                aircraft: recording.aircraft,
+                item: Rc::clone(&recording.item),
+            },
+        )?);
+    }
+
+    Ok(commands)
+}
+
+impl Drop for TweaksPlugin {
+    fn drop(&mut self) {
+        // Don't leave any overrides behind when unloaded without disable
+        self.components.borrow_mut().teardown();
+    }
+}